@@ -1,126 +1,247 @@
-use crate::appdata::{load_cookies, save_cookies};
-use anyhow::{anyhow, bail, Result};
-use reqwest::{header::HeaderMap, redirect::Policy, ClientBuilder, StatusCode};
+use crate::{
+    appdata::{load_cookies, remove_cookies, save_cookies},
+    Error, Result,
+};
+use config::ClientConfig;
+use futures::Future;
+use model::volume_id::VolumeID;
+use page::Message;
+use rate_limit::RateLimiter;
+use reqwest::{
+    cookie::Jar, header::LOCATION, redirect::Policy, ClientBuilder, Response, StatusCode, Url,
+};
 use scraper::{Html, Selector};
-use thiserror::Error;
+use stat::ListingCache;
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
 
+pub mod config;
 pub mod download;
 pub mod list;
+pub mod mkdir;
+#[cfg(any(test, feature = "test-util"))]
+pub mod mock;
 pub mod model;
+mod page;
+pub mod rate_limit;
+pub mod remove;
+pub mod rename;
+pub mod stat;
+pub mod upload;
+pub mod walk;
 
-#[derive(Error, Debug)]
-pub enum Error {
-    #[error("invalid username or password")]
-    InvalidUsernameOrPassword,
-    #[error("{0} was not found")]
-    AttrValueNotFound(String),
-    #[error("Response status was not {0}")]
-    InvalidResponseStatus(StatusCode),
-    #[error("unknown error")]
-    Unknown,
-}
-
+/// a client of the file browser of the VPN portal
 pub struct Client {
     http: reqwest::Client,
+    cookies: Arc<Jar>,
+    config: ClientConfig,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    listing_cache: ListingCache,
+    /// whether the cookies of a new session are saved for the next run, see `persist_cookies`
+    persist_cookies: bool,
+    /// the username and password of the last login, to log in again when the session expires
+    credentials: Mutex<Option<(String, String)>>,
+    /// the number of times the client has logged in again.
+    /// requests which fail at the same time log in again only once.
+    renewals: tokio::sync::Mutex<u64>,
 }
 
 impl Client {
+    /// make client for the university VPN without a session.
     pub fn new() -> Result<Self> {
+        Self::with_config(ClientConfig::default())
+    }
+
+    /// make client which signs in to the portal of `config`.
+    /// the cookies of the client are not saved unless `persist_cookies` is called.
+    pub fn with_config(config: ClientConfig) -> Result<Self> {
+        let cookies = Arc::new(Jar::default());
         Ok(Self {
-            http: default_http_builder().build()?,
+            http: default_http_builder(&config, cookies.clone()).build()?,
+            cookies,
+            config,
+            rate_limiter: None,
+            listing_cache: ListingCache::default(),
+            persist_cookies: false,
+            credentials: Mutex::new(None),
+            renewals: tokio::sync::Mutex::new(0),
+        })
+    }
+
+    /// make client which sends requests to `base_url` instead of the university VPN.
+    pub fn with_base_url(base_url: Url) -> Result<Self> {
+        Self::with_config(ClientConfig {
+            base_url,
+            ..ClientConfig::default()
         })
     }
 
-    pub async fn with_login(username: &str, password: &str) -> Result<Self> {
-        let client = Self::new()?;
+    /// make client and log in.
+    pub async fn with_login(config: ClientConfig, username: &str, password: &str) -> Result<Self> {
+        let client = Self::with_config(config)?;
         client.login(username, password).await?;
         Ok(client)
     }
 
+    /// make client with the cookies saved by the last login, which may have expired.
+    /// the cookies of a new session are saved in place of them.
+    pub fn with_token(config: ClientConfig) -> Result<Self> {
+        let mut client = Self::with_config(config)?;
+        client.persist_cookies(true);
+        for cookie in &load_cookies()? {
+            client
+                .cookies
+                .add_cookie_str(cookie, &client.config.base_url);
+        }
+        Ok(client)
+    }
+
     /// make client with token.
     /// if token is invalid, make client with login.
-    pub async fn with_token_or_login(username: &str, password: &str) -> Result<Self> {
-        let cookies = match load_cookies() {
-            Ok(cookies) => cookies,
+    /// the cookies of the session are saved in the cache dir for the next run.
+    pub async fn with_token_or_login(
+        config: ClientConfig,
+        username: &str,
+        password: &str,
+    ) -> Result<Self> {
+        let client = match Self::with_token(config.clone()) {
+            Ok(client) => client,
             Err(_) => {
-                return Self::with_login(username, password).await;
+                let mut client = Self::with_config(config)?;
+                client.persist_cookies(true);
+                client.login(username, password).await?;
+                return Ok(client);
             }
         };
-
-        let mut header = HeaderMap::new();
-        header.insert("cookie", cookies.join("; ").parse().unwrap());
-        let http = default_http_builder().default_headers(header).build()?;
-        let client = Self { http };
-        if let Err(_e) = client.check_cookies().await {
-            Self::with_login(username, password).await
+        if client.check_cookies().await.is_ok() {
+            *client.credentials.lock().unwrap() =
+                Some((username.to_string(), password.to_string()));
         } else {
-            Ok(client)
+            client.login(username, password).await?;
         }
+        Ok(client)
+    }
+
+    /// saves the cookies of new sessions to the cache dir, so that `with_token` can reuse them.
+    /// logging out removes them.
+    pub fn persist_cookies(&mut self, persist: bool) {
+        self.persist_cookies = persist;
     }
 
+    /// limits the total throughput of all transfers made by this client.
+    pub fn limit_rate(&mut self, bytes_per_sec: u64) {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(bytes_per_sec)));
+    }
+
+    /// returns an error unless the session of the client is valid.
     pub async fn check_cookies(&self) -> Result<()> {
+        self.xsauth().await?;
+        Ok(())
+    }
+
+    /// the token which the web UI sends with requests that modify files.
+    /// it is found only in pages for a valid session.
+    async fn xsauth(&self) -> Result<String> {
         let resp = self
             .http
-            .get("https://vpn.inf.shizuoka.ac.jp/dana/home/index.cgi")
+            .get(self.endpoint("/dana/home/index.cgi"))
             .send()
             .await?;
-        Html::parse_document(resp.text().await?.as_str())
-            .select(&Selector::parse("#xsauth_395").unwrap())
+        if is_sign_in_redirect(&resp) {
+            return Err(Error::SessionExpired);
+        }
+        let xsauth = Html::parse_document(resp.text().await?.as_str())
+            .select(&Selector::parse(r#"#xsauth_395, input[name="xsauth"]"#).unwrap())
             .next()
-            .ok_or_else(|| Error::AttrValueNotFound("xsauth".to_string()))?
-            .value()
-            .attr("value")
-            .ok_or_else(|| Error::AttrValueNotFound("xsauth".to_string()))?;
-        Ok(())
+            .and_then(|input| input.value().attr("value"))
+            .ok_or_else(|| Error::parse("xsauth"))?
+            .to_string();
+        Ok(xsauth)
     }
 
+    /// log in and start a session. if another session is active, it is continued.
+    /// when the session expires later, the client logs in again with the same account.
     pub async fn login(&self, username: &str, password: &str) -> Result<()> {
+        self.login_once(username, password).await?;
+        *self.credentials.lock().unwrap() = Some((username.to_string(), password.to_string()));
+        Ok(())
+    }
+
+    async fn login_once(&self, username: &str, password: &str) -> Result<()> {
+        let tz_offset = self.config.tz_offset.to_string();
         let resp = self
             .http
-            .post("https://vpn.inf.shizuoka.ac.jp/dana-na/auth/url_3/login.cgi")
+            .post(self.endpoint(&self.config.auth_endpoint("login.cgi")))
             .form(&[
-                ("tz_offset", "540"),
+                ("tz_offset", tz_offset.as_str()),
                 ("username", username),
                 ("password", password),
-                ("realm", "Student-Realm"),
+                ("realm", self.config.realm.as_str()),
                 ("btnSubmit", "Sign+In"),
             ])
             .send()
             .await?;
         if resp.status() != StatusCode::FOUND {
-            bail!(Error::InvalidResponseStatus(StatusCode::FOUND))
+            return Err(Error::Http {
+                status: resp.status(),
+            });
         }
 
-        match resp.headers().get("location").unwrap().to_str()? {
+        match location(&resp)?.as_str() {
             "/dana/home/index.cgi" => {
                 let cookies = resp
                     .cookies()
                     .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
                     .collect::<Vec<_>>();
-                save_cookies(&cookies)?;
+                if self.persist_cookies {
+                    save_cookies(&cookies)?;
+                }
                 Ok(())
             }
-            "/dana-na/auth/url_3/welcome.cgi?p=failed" => {
-                Err(anyhow!(Error::InvalidUsernameOrPassword))
+            location if location == self.config.auth_endpoint("welcome.cgi?p=failed") => {
+                Err(Error::Auth)
             }
             _ => {
-                let html = Html::parse_document(resp.text().await?.as_str());
-                let form_data_str = html
+                // the parsed page is not `Send`, so it must be dropped before awaiting
+                let form_data_str = Html::parse_document(resp.text().await?.as_str())
                     .select(&Selector::parse("#DSIDFormDataStr").unwrap())
                     .next()
-                    .ok_or_else(|| Error::AttrValueNotFound("DSIDFormDataStr".to_string()))?
-                    .value()
-                    .attr("value")
-                    .ok_or_else(|| Error::AttrValueNotFound("DSIDFormDataStr".to_string()))?;
-                self.continue_current_session(form_data_str).await
+                    .and_then(|input| input.value().attr("value"))
+                    .ok_or_else(|| Error::parse("DSIDFormDataStr"))?
+                    .to_string();
+                self.continue_current_session(&form_data_str).await
             }
         }
     }
 
+    /// log out and end the session, so that the portal does not count it as an active one.
+    /// the saved cookies are removed, and the client does not log in again by itself.
+    pub async fn logout(&self) -> Result<()> {
+        let resp = self
+            .http
+            .get(self.endpoint("/dana-na/auth/logout.cgi"))
+            .send()
+            .await?;
+        // the portal redirects to the sign-in page after logging out
+        if !resp.status().is_success() && !resp.status().is_redirection() {
+            return Err(Error::Http {
+                status: resp.status(),
+            });
+        }
+        *self.credentials.lock().unwrap() = None;
+        if self.persist_cookies {
+            remove_cookies()?;
+        }
+        Ok(())
+    }
+
+    /// continue the session which is active in another browser or device.
     pub async fn continue_current_session(&self, form_data_str: &str) -> Result<()> {
         let resp = self
             .http
-            .post("https://vpn.inf.shizuoka.ac.jp/dana-na/auth/url_3/login.cgi")
+            .post(self.endpoint(&self.config.auth_endpoint("login.cgi")))
             .form(&[
                 ("btnContinue", "セッションを続行します"),
                 ("FormDataStr", form_data_str),
@@ -128,25 +249,216 @@ impl Client {
             .send()
             .await?;
         if resp.status() != StatusCode::FOUND {
-            bail!(Error::InvalidResponseStatus(StatusCode::FOUND))
+            return Err(Error::Http {
+                status: resp.status(),
+            });
         }
 
-        match resp.headers().get("location").unwrap().to_str()? {
+        match location(&resp)?.as_str() {
             "/dana/home/index.cgi" => {
                 let cookies = resp
                     .cookies()
                     .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
                     .collect::<Vec<_>>();
-                save_cookies(&cookies)?;
+                if self.persist_cookies {
+                    save_cookies(&cookies)?;
+                }
                 Ok(())
             }
-            _ => Err(anyhow!(Error::Unknown)),
+            _ => Err(Error::Unknown),
+        }
+    }
+}
+
+impl Client {
+    /// `path` must start with '/'
+    fn endpoint(&self, path: &str) -> String {
+        format!(
+            "{}{}",
+            self.config.base_url.as_str().trim_end_matches('/'),
+            path
+        )
+    }
+
+    /// runs `f`. if it fails because the session expired, logs in again and retries once.
+    async fn with_session<T, F, Fut>(&self, f: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let renewals = *self.renewals.lock().await;
+        match f().await {
+            Err(Error::SessionExpired) => {
+                self.renew(renewals).await?;
+                f().await
+            }
+            result => result,
+        }
+    }
+
+    /// logs in again unless another request has done it since the `renewals`-th renewal.
+    async fn renew(&self, renewals: u64) -> Result<()> {
+        let mut current = self.renewals.lock().await;
+        if *current != renewals {
+            return Ok(());
+        }
+        let (username, password) = self
+            .credentials
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or(Error::SessionExpired)?;
+        self.login_once(&username, &password).await?;
+        *current += 1;
+        Ok(())
+    }
+
+    /// post the form of the file browser which modifies `dir` (e.g. making a directory)
+    /// and returns the error message of the result page, if any.
+    async fn post_wfb(
+        &self,
+        dir: &Path,
+        volume_id: &VolumeID,
+        fields: &[(&str, &str)],
+    ) -> Result<Option<Message>> {
+        self.with_session(|| self.post_wfb_once(dir, volume_id, fields))
+            .await
+    }
+
+    async fn post_wfb_once(
+        &self,
+        dir: &Path,
+        volume_id: &VolumeID,
+        fields: &[(&str, &str)],
+    ) -> Result<Option<Message>> {
+        let xsauth = self.xsauth().await?;
+        let volume_id = volume_id.to_string();
+        let mut form = vec![
+            ("t", "p"),
+            ("v", volume_id.as_str()),
+            ("dir", dir.to_str().unwrap()),
+            ("xsauth", xsauth.as_str()),
+        ];
+        form.extend_from_slice(fields);
+        self.listing_cache.invalidate(dir, &volume_id);
+        let resp = self
+            .http
+            .post(self.endpoint("/dana/fb/smb/wfb.cgi"))
+            .form(&form)
+            .send()
+            .await?;
+
+        // the web UI is redirected to the listing of `dir` on success
+        match resp.status() {
+            _ if is_sign_in_redirect(&resp) => Err(Error::SessionExpired),
+            StatusCode::FOUND => Ok(None),
+            StatusCode::OK => Message::find(&resp.text().await?),
+            status => Err(Error::Http { status }),
         }
     }
 }
 
-fn default_http_builder() -> ClientBuilder {
-    reqwest::ClientBuilder::new()
+fn default_http_builder(config: &ClientConfig, cookies: Arc<Jar>) -> ClientBuilder {
+    let builder = reqwest::ClientBuilder::new()
         .redirect(Policy::none())
-        .cookie_store(true)
+        .cookie_provider(cookies);
+    match &config.user_agent {
+        Some(user_agent) => builder.user_agent(user_agent),
+        None => builder,
+    }
+}
+
+fn location(resp: &Response) -> Result<String> {
+    Ok(resp
+        .headers()
+        .get(LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .ok_or_else(|| Error::parse("location header"))?
+        .to_string())
+}
+
+/// the portal redirects requests without a valid session to the sign-in pages
+fn is_sign_in_redirect(resp: &Response) -> bool {
+    resp.status().is_redirection()
+        && location(resp).is_ok_and(|location| location.contains("/dana-na/auth/"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        mock::{listing, serve, Response},
+        model::volume_id::VolumeID,
+        Client,
+    };
+    use crate::Error;
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// the session expires after the first login, and the second login has to continue
+    /// the session which the server thinks is still active
+    #[tokio::test]
+    pub async fn relogin_on_expired_session() {
+        let logged_in = Arc::new(AtomicBool::new(false));
+        let n_logins = Arc::new(AtomicUsize::new(0));
+        let (logged_in_by_server, n_logins_by_server) = (logged_in.clone(), n_logins.clone());
+        let base_url = serve(move |req| {
+            if req.target == "/dana-na/auth/url_3/login.cgi" {
+                if req.body_text().contains("FormDataStr=dsid") {
+                    logged_in_by_server.store(true, Ordering::Relaxed);
+                    return Response::redirect("/dana/home/index.cgi");
+                }
+                if n_logins_by_server.fetch_add(1, Ordering::Relaxed) == 0 {
+                    logged_in_by_server.store(true, Ordering::Relaxed);
+                    return Response::redirect("/dana/home/index.cgi");
+                }
+                let mut resp = Response::redirect("/dana-na/auth/url_3/welcome.cgi?p=user-confirm");
+                resp.body = br#"<input id="DSIDFormDataStr" value="dsid">"#.to_vec();
+                return resp;
+            }
+            if !logged_in_by_server.load(Ordering::Relaxed) {
+                return Response::redirect("/dana-na/auth/url_3/welcome.cgi");
+            }
+            listing(&[], &["a.txt"])
+        })
+        .await;
+        let c = Client::with_base_url(base_url).unwrap();
+        c.login("user", "pass").await.unwrap();
+
+        logged_in.store(false, Ordering::Relaxed);
+        let segments = c.list("/dir", &VolumeID::FSShare).await.unwrap();
+        assert_eq!(segments[0].name, "a.txt");
+        assert_eq!(n_logins.load(Ordering::Relaxed), 2);
+    }
+
+    /// the client does not log in again after logging out
+    #[tokio::test]
+    pub async fn logout() {
+        let logged_in = Arc::new(AtomicBool::new(false));
+        let logged_in_by_server = logged_in.clone();
+        let base_url = serve(move |req| match req.target.as_str() {
+            "/dana-na/auth/url_3/login.cgi" => {
+                logged_in_by_server.store(true, Ordering::Relaxed);
+                Response::redirect("/dana/home/index.cgi")
+            }
+            "/dana-na/auth/logout.cgi" => {
+                logged_in_by_server.store(false, Ordering::Relaxed);
+                Response::redirect("/dana-na/auth/url_3/welcome.cgi?p=logout")
+            }
+            _ if !logged_in_by_server.load(Ordering::Relaxed) => {
+                Response::redirect("/dana-na/auth/url_3/welcome.cgi")
+            }
+            _ => listing(&[], &[]),
+        })
+        .await;
+        let c = Client::with_base_url(base_url).unwrap();
+        c.login("user", "pass").await.unwrap();
+        c.logout().await.unwrap();
+
+        assert!(!logged_in.load(Ordering::Relaxed));
+        let err = c.list("/dir", &VolumeID::FSShare).await.unwrap_err();
+        assert!(matches!(err, Error::SessionExpired));
+        assert!(!logged_in.load(Ordering::Relaxed));
+    }
 }
@@ -0,0 +1,85 @@
+//! the portal to sign in to
+
+use reqwest::Url;
+
+/// the portal of the Faculty of Informatics
+pub const DEFAULT_BASE_URL: &str = "https://vpn.inf.shizuoka.ac.jp";
+/// the sign-in URL of the student realm
+pub const DEFAULT_AUTH_PATH: &str = "url_3";
+/// the realm of students
+pub const DEFAULT_REALM: &str = "Student-Realm";
+/// JST in minutes
+pub const DEFAULT_TZ_OFFSET: i32 = 540;
+
+/// where and how the client signs in to the Pulse Secure portal
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// the URL of the portal, e.g. `https://vpn.inf.shizuoka.ac.jp`
+    pub base_url: Url,
+    /// the sign-in URL of the realm, e.g. `url_3` of `/dana-na/auth/url_3/login.cgi`
+    pub auth_path: String,
+    /// e.g. `Student-Realm`. staff accounts sign in to another realm.
+    pub realm: String,
+    /// the offset of the local time from UTC in minutes, which the login form sends
+    pub tz_offset: i32,
+    /// if `None`, no `User-Agent` header is sent
+    pub user_agent: Option<String>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            base_url: Url::parse(DEFAULT_BASE_URL).unwrap(),
+            auth_path: DEFAULT_AUTH_PATH.to_string(),
+            realm: DEFAULT_REALM.to_string(),
+            tz_offset: DEFAULT_TZ_OFFSET,
+            user_agent: None,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// `/dana-na/auth/<auth_path>/<page>`
+    pub(super) fn auth_endpoint(&self, page: &str) -> String {
+        format!("/dana-na/auth/{}/{}", self.auth_path, page)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ClientConfig;
+    use crate::api::{
+        mock::{serve, Response},
+        Client,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    pub async fn login_with_config() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let requests_by_server = requests.clone();
+        let base_url = serve(move |req| {
+            requests_by_server
+                .lock()
+                .unwrap()
+                .push((req.target.clone(), req.body_text()));
+            Response::redirect("/dana/home/index.cgi")
+        })
+        .await;
+        let c = Client::with_config(ClientConfig {
+            base_url,
+            auth_path: "url_5".to_string(),
+            realm: "Staff-Realm".to_string(),
+            tz_offset: -300,
+            user_agent: Some("cvpn-test".to_string()),
+        })
+        .unwrap();
+        c.login("user", "pass").await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        let (target, body) = &requests[0];
+        assert_eq!(target, "/dana-na/auth/url_5/login.cgi");
+        assert!(body.contains("realm=Staff-Realm"));
+        assert!(body.contains("tz_offset=-300"));
+    }
+}
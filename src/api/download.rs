@@ -1,80 +1,273 @@
-use super::{model::volume_id::VolumeID, Client};
-use anyhow::{bail, Result};
+//! downloading files, optionally from an offset
+
+use super::{
+    is_sign_in_redirect,
+    model::{size::Size, volume_id::VolumeID},
+    page::is_sign_in_page,
+    rate_limit::RateLimiter,
+    Client,
+};
+use crate::{Error, Result};
+use bytes::Bytes;
 use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
-use reqwest::{StatusCode, Url};
-use std::{path::PathBuf, str::FromStr};
-
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("Permission denied")]
-    PermissionDenied,
-    #[error("Response status was not {0}")]
-    InvalidResponseStatus(StatusCode),
-    #[error("The file could not be found. The argument `path` or `volume` may be wrong.")]
-    NotFound,
-}
+use reqwest::{
+    header::{CONTENT_TYPE, RANGE},
+    Response, StatusCode, Url,
+};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 const FILENAME_ASCIISET: &AsciiSet = &CONTROLS.add(b'+');
 
+/// the body of a downloaded file, read chunk by chunk
+pub struct DownloadStream {
+    offset: u64,
+    content_length: Option<u64>,
+    body: Body,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+enum Body {
+    Response(Response),
+    /// already read to detect error pages
+    Buffered(Option<Bytes>),
+}
+
+impl DownloadStream {
+    /// the position in the file where the body starts.
+    /// this is 0 if the server ignored the requested range.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// the length of the body (not of the whole file)
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// the exact size of the whole file, if the server sent `Content-Length`
+    pub fn file_size(&self) -> Option<Size> {
+        self.content_length
+            .map(|content_length| Size::from_bytes(self.offset + content_length))
+    }
+
+    /// returns the next chunk of the body, or `None` at the end of the body
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        let chunk = match &mut self.body {
+            Body::Response(resp) => resp.chunk().await?,
+            Body::Buffered(bytes) => bytes.take(),
+        };
+        if let (Some(rate_limiter), Some(chunk)) = (&self.rate_limiter, &chunk) {
+            rate_limiter.acquire(chunk.len() as u64).await;
+        }
+        Ok(chunk)
+    }
+
+    /// writes the rest of the body into `w` and returns the number of written bytes
+    pub async fn write_to<W: AsyncWrite + Unpin>(&mut self, w: &mut W) -> Result<u64> {
+        let mut written = 0;
+        while let Some(chunk) = self.chunk().await? {
+            w.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        w.flush().await?;
+        Ok(written)
+    }
+}
+
 impl Client {
+    /// downloads the file `dirp/filename` into memory.
     pub async fn download<P: Into<PathBuf>>(
         &self,
         dirp: P,
         filename: &str,
         volume_id: &VolumeID,
     ) -> Result<Vec<u8>> {
+        let mut stream = self.download_stream(dirp, filename, volume_id).await?;
+        let mut content = Vec::with_capacity(stream.content_length().unwrap_or(0) as usize);
+        while let Some(chunk) = stream.chunk().await? {
+            content.extend_from_slice(&chunk);
+        }
+        Ok(content)
+    }
+
+    /// same as `download`, but the body is not buffered in memory.
+    pub async fn download_stream<P: Into<PathBuf>>(
+        &self,
+        dirp: P,
+        filename: &str,
+        volume_id: &VolumeID,
+    ) -> Result<DownloadStream> {
+        self.download_stream_from(dirp, filename, volume_id, 0)
+            .await
+    }
+
+    /// same as `download_stream`, but requests the file from `offset` with a `Range` header.
+    /// if the server does not honor the range, the whole file is returned and
+    /// `DownloadStream::offset` is 0.
+    pub async fn download_stream_from<P: Into<PathBuf>>(
+        &self,
+        dirp: P,
+        filename: &str,
+        volume_id: &VolumeID,
+        offset: u64,
+    ) -> Result<DownloadStream> {
         let dir: PathBuf = dirp.into();
+        self.with_session(|| self.download_stream_once(&dir, filename, volume_id, offset))
+            .await
+    }
+
+    async fn download_stream_once(
+        &self,
+        dir: &Path,
+        filename: &str,
+        volume_id: &VolumeID,
+        offset: u64,
+    ) -> Result<DownloadStream> {
+        let path = dir.join(filename);
         let filename = utf8_percent_encode(filename, FILENAME_ASCIISET);
-        let url = Url::from_str(
-            &format!("https://vpn.inf.shizuoka.ac.jp/dana/download/{}?url=/dana-cached/fb/smb/wfv.cgi?t=p&v={}&si=&ri=&pi=&ignoreDfs=1&dir={}&file={}",
-                filename,
-                volume_id.to_string().as_str(),
-                utf8_percent_encode(dir.to_str().unwrap().trim_matches('/').replace('/', "\\").as_str(), NON_ALPHANUMERIC),
-                filename,
+        let url = Url::from_str(&format!(
+            "{}?url=/dana-cached/fb/smb/wfv.cgi?t=p&v={}&si=&ri=&pi=&ignoreDfs=1&dir={}&file={}",
+            self.endpoint(&format!("/dana/download/{}", filename)),
+            volume_id.to_string().as_str(),
+            utf8_percent_encode(
+                dir.to_str()
+                    .unwrap()
+                    .trim_matches('/')
+                    .replace('/', "\\")
+                    .as_str(),
+                NON_ALPHANUMERIC
             ),
-        )?;
+            filename,
+        ))
+        .map_err(|_| Error::InvalidArgument(format!("invalid path '{}'", path.display())))?;
 
-        let resp = self.http.get(url).send().await?;
-        if resp.status() != StatusCode::OK {
-            bail!(Error::InvalidResponseStatus(StatusCode::OK))
+        let mut req = self.http.get(url.clone());
+        if offset > 0 {
+            req = req.header(RANGE, format!("bytes={}-", offset));
         }
+        let mut resp = req.send().await?;
+        if resp.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            // the file on the server may have been replaced with a smaller one
+            resp = self.http.get(url).send().await?;
+        }
+        let offset = match resp.status() {
+            StatusCode::OK => 0,
+            StatusCode::PARTIAL_CONTENT if offset > 0 => offset,
+            _ if is_sign_in_redirect(&resp) => return Err(Error::SessionExpired),
+            status => return Err(Error::Http { status }),
+        };
 
+        let content_length = resp.content_length();
         let is_html = resp
             .headers()
-            .get("Content-Type")
-            .unwrap()
-            .to_str()?
-            .contains("html");
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("html"));
         if is_html {
             const MESSAGE_NOT_FOUND: &str = "The file or folder does not exist on the server.";
             const MESSAGE_PERMISSION_DENIED: &str =
                 "You do not have permission to access this file server.";
-            let content = resp.bytes().await?.to_vec();
+            let content = resp.bytes().await?;
             let content_html = String::from_utf8_lossy(&content);
-            if content_html.contains(MESSAGE_NOT_FOUND) {
-                bail!(Error::NotFound)
+            if is_sign_in_page(&content_html) {
+                return Err(Error::SessionExpired);
+            } else if content_html.contains(MESSAGE_NOT_FOUND) {
+                return Err(Error::NotFound(path));
             } else if content_html.contains(MESSAGE_PERMISSION_DENIED) {
-                bail!(Error::PermissionDenied)
+                return Err(Error::PermissionDenied(path));
             } else {
-                return Ok(content.to_vec());
+                return Ok(DownloadStream {
+                    offset,
+                    content_length,
+                    body: Body::Buffered(Some(content)),
+                    rate_limiter: self.rate_limiter.clone(),
+                });
             }
         }
 
-        Ok(resp.bytes().await?.to_vec())
+        Ok(DownloadStream {
+            offset,
+            content_length,
+            body: Body::Response(resp),
+            rate_limiter: self.rate_limiter.clone(),
+        })
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
-        api::{model::volume_id::VolumeID, Client},
+        api::{
+            config::ClientConfig,
+            mock::{self, Response},
+            model::volume_id::VolumeID,
+            Client,
+        },
         appdata::load_account_info,
     };
+    use reqwest::Url;
+
+    const CONTENT: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    /// serves `CONTENT` for any request.
+    /// if `honor_range` is true, `Range: bytes=N-` is answered with 206.
+    async fn serve(honor_range: bool) -> Url {
+        mock::serve(move |req| {
+            let offset = req
+                .header("range")
+                .and_then(|range| range.strip_prefix("bytes="))
+                .map(|range| range.trim_end_matches('-').parse::<usize>().unwrap())
+                .filter(|_| honor_range);
+            let (status, body) = match offset {
+                Some(offset) => ("206 Partial Content", &CONTENT[offset..]),
+                None => ("200 OK", CONTENT),
+            };
+            Response {
+                status,
+                headers: vec![("Content-Type", "application/octet-stream".to_string())],
+                body: body.to_vec(),
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    pub async fn download_stream_from_range() {
+        let c = Client::with_base_url(serve(true).await).unwrap();
+        let mut stream = c
+            .download_stream_from("/dir", "file.txt", &VolumeID::FSShare, 10)
+            .await
+            .unwrap();
+        assert_eq!(stream.offset(), 10);
+        let mut bytes = Vec::new();
+        stream.write_to(&mut bytes).await.unwrap();
+        assert_eq!(bytes, &CONTENT[10..]);
+    }
+
+    #[tokio::test]
+    pub async fn download_stream_from_range_ignored() {
+        let c = Client::with_base_url(serve(false).await).unwrap();
+        let mut stream = c
+            .download_stream_from("/dir", "file.txt", &VolumeID::FSShare, 10)
+            .await
+            .unwrap();
+        assert_eq!(stream.offset(), 0);
+        let mut bytes = Vec::new();
+        stream.write_to(&mut bytes).await.unwrap();
+        assert_eq!(bytes, CONTENT);
+    }
 
     #[tokio::test]
     pub async fn download() {
         let (u, p) = load_account_info().unwrap();
-        let c = Client::with_token_or_login(&u, &p).await.unwrap();
+        let c = Client::with_token_or_login(ClientConfig::default(), &u, &p)
+            .await
+            .unwrap();
         let bytes = c
             .download(
                 "/class/2022記号処理",
@@ -1,18 +1,25 @@
+//! listing a directory
+
 use super::{
+    is_sign_in_redirect,
     model::{
         segment::Segment,
         size::{Size, Unit},
         volume_id::VolumeID,
     },
+    page::is_sign_in_page,
     Client,
 };
-use anyhow::{anyhow, bail, Result};
+use crate::{Error, Result};
 use chrono::NaiveDateTime;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use reqwest::{StatusCode, Url};
 use scraper::{Html, Selector};
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 const SEGMENTS_CAPASITY: usize = 256;
 const DATETIME_FORMAT: &str = "%a %b  %d %H:%M:%S %Y";
@@ -20,18 +27,15 @@ static DIR_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"d\("(.+)","(.+)","(.+
 static FILE_REGEX: Lazy<Regex> =
     Lazy::new(|| Regex::new(r#"f\("(.+)","(.+)","(.+)","(.+)"\);"#).unwrap());
 
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("cannot fetch directory '{0}': Permission denied")]
-    PermissionDenied(String),
-    #[error("unknown error")]
-    Unknown,
-}
-
 impl Client {
+    /// lists the files and directories in `p`.
     pub async fn list<P: Into<PathBuf>>(&self, p: P, volume_id: &VolumeID) -> Result<Vec<Segment>> {
-        let mut u = Url::from_str("https://vpn.inf.shizuoka.ac.jp/dana/fb/smb/wfb.cgi").unwrap();
         let path: PathBuf = p.into();
+        self.with_session(|| self.list_once(&path, volume_id)).await
+    }
+
+    async fn list_once(&self, path: &Path, volume_id: &VolumeID) -> Result<Vec<Segment>> {
+        let mut u = Url::from_str(&self.endpoint("/dana/fb/smb/wfb.cgi")).unwrap();
 
         u.query_pairs_mut()
             .append_pair("t", "p")
@@ -43,19 +47,23 @@ impl Client {
             .append_pair("so", "asc")
             .append_pair("dir", path.to_str().unwrap());
         let resp = self.http.get(u).send().await?;
-        if resp.status() != StatusCode::OK {
-            if resp.status() == StatusCode::FOUND {
-                bail!(Error::PermissionDenied(path.to_str().unwrap().to_string()))
-            } else {
-                bail!(Error::Unknown)
-            }
+        match resp.status() {
+            StatusCode::OK => (),
+            _ if is_sign_in_redirect(&resp) => return Err(Error::SessionExpired),
+            // the web UI is redirected to the home page if the directory cannot be read
+            StatusCode::FOUND => return Err(Error::PermissionDenied(path.to_path_buf())),
+            status => return Err(Error::Http { status }),
         }
 
-        let doc = Html::parse_document(resp.text().await?.as_str());
+        let html = resp.text().await?;
+        if is_sign_in_page(&html) {
+            return Err(Error::SessionExpired);
+        }
+        let doc = Html::parse_document(&html);
         let elem = doc
             .select(&Selector::parse("table#table_wfb_5 > tbody > script").unwrap())
             .next()
-            .ok_or_else(|| anyhow!("No such directory"))?
+            .ok_or_else(|| Error::NotFound(path.to_path_buf()))?
             .text()
             .collect::<String>();
         let lines = elem.split('\n').collect::<Vec<_>>();
@@ -71,7 +79,7 @@ impl Client {
                     tokens[1].to_string(),
                     path.join(&tokens[1]),
                     volume_id.to_string(),
-                    NaiveDateTime::parse_from_str(&tokens[3], DATETIME_FORMAT)?,
+                    parse_datetime(&tokens[3])?,
                 ))
             }
             if let Some(tokens) = FILE_REGEX.captures_iter(line).next() {
@@ -79,9 +87,14 @@ impl Client {
                 segments.push(Segment::from_file(
                     tokens[1].to_string(),
                     path.join(&tokens[1]),
-                    Size::new(size_tokens[0].parse()?, Unit::from_str(size_tokens[1])),
+                    Size::new(
+                        size_tokens[0]
+                            .parse()
+                            .map_err(|_| Error::parse(format!("size '{}'", &tokens[3])))?,
+                        Unit::from_symbol(size_tokens[1]),
+                    ),
                     volume_id.to_string(),
-                    NaiveDateTime::parse_from_str(&tokens[4], DATETIME_FORMAT)?,
+                    parse_datetime(&tokens[4])?,
                 ))
             }
         }
@@ -89,3 +102,8 @@ impl Client {
         Ok(segments)
     }
 }
+
+fn parse_datetime(s: &str) -> Result<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, DATETIME_FORMAT)
+        .map_err(|_| Error::parse(format!("date '{}'", s)))
+}
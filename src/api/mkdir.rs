@@ -0,0 +1,24 @@
+//! making directories
+
+use super::{model::volume_id::VolumeID, Client};
+use crate::Result;
+use std::path::PathBuf;
+
+impl Client {
+    /// make the directory `name` in the directory `dirp`
+    pub async fn mkdir<P: Into<PathBuf>>(
+        &self,
+        dirp: P,
+        name: &str,
+        volume_id: &VolumeID,
+    ) -> Result<()> {
+        let dir: PathBuf = dirp.into();
+        let message = self
+            .post_wfb(&dir, volume_id, &[("acttype", "create"), ("newname", name)])
+            .await?;
+        match message {
+            Some(message) => Err(message.into_error(dir.join(name))),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! a minimal HTTP server which stands in for the Pulse Secure endpoints in tests
+
+use reqwest::Url;
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// a request received by the server
+pub struct Request {
+    /// e.g. `GET`
+    pub method: String,
+    /// the path with the query
+    pub target: String,
+    /// the names are in lowercase
+    pub headers: Vec<(String, String)>,
+    /// the content, which is decoded if it was chunked
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// the value of the header `name`, which is case-insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// the body as text, e.g. the form data
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+/// a response sent by the server. `Content-Length` is added to `headers`.
+pub struct Response {
+    /// e.g. `200 OK`
+    pub status: &'static str,
+    /// the names and values of the headers
+    pub headers: Vec<(&'static str, String)>,
+    /// the content
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// a page of the portal
+    pub fn html(body: &str) -> Self {
+        Self {
+            status: "200 OK",
+            headers: vec![("Content-Type", "text/html; charset=utf-8".to_string())],
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    /// a redirect to `location`, which is how the portal answers most forms
+    pub fn redirect(location: &str) -> Self {
+        Self {
+            status: "302 Found",
+            headers: vec![("Location", location.to_string())],
+            body: Vec::new(),
+        }
+    }
+}
+
+/// the page which has the xsauth token of a valid session
+pub const HOME_PAGE: &str = r#"<html><body><input id="xsauth_395" value="token"></body></html>"#;
+
+/// serves responses made by `handler` and returns the base URL of the server
+pub async fn serve<F>(handler: F) -> Url
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        loop {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let mut buf = Vec::new();
+                let mut chunk = [0; 4096];
+                let header_end = loop {
+                    if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break i + 4;
+                    }
+                    let n = sock.read(&mut chunk).await.unwrap();
+                    if n == 0 {
+                        return;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                };
+                let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+                let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+                let method = request_line.next().unwrap_or_default().to_string();
+                let target = request_line.next().unwrap_or_default().to_string();
+                let headers = head
+                    .lines()
+                    .skip(1)
+                    .filter_map(|line| line.split_once(':'))
+                    .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+                    .collect::<Vec<_>>();
+                let header = |name: &str| {
+                    headers
+                        .iter()
+                        .find(|(n, _)| n == name)
+                        .map(|(_, value)| value.as_str())
+                };
+                let chunked = header("transfer-encoding") == Some("chunked");
+                let content_length = header("content-length")
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .unwrap_or(0);
+
+                let mut body = buf[header_end..].to_vec();
+                if chunked {
+                    while !body.ends_with(b"0\r\n\r\n") {
+                        let n = sock.read(&mut chunk).await.unwrap();
+                        body.extend_from_slice(&chunk[..n]);
+                    }
+                    body = dechunk(&body);
+                } else {
+                    while body.len() < content_length {
+                        let n = sock.read(&mut chunk).await.unwrap();
+                        body.extend_from_slice(&chunk[..n]);
+                    }
+                }
+
+                let resp = handler(&Request {
+                    method,
+                    target,
+                    headers,
+                    body,
+                });
+                let mut head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    resp.status,
+                    resp.body.len()
+                );
+                for (name, value) in &resp.headers {
+                    head.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                head.push_str("\r\n");
+                sock.write_all(head.as_bytes()).await.unwrap();
+                sock.write_all(&resp.body).await.unwrap();
+            });
+        }
+    });
+    Url::parse(&format!("http://{}", addr)).unwrap()
+}
+
+fn dechunk(mut body: &[u8]) -> Vec<u8> {
+    let mut content = Vec::new();
+    while let Some(i) = body.windows(2).position(|w| w == b"\r\n") {
+        let len = usize::from_str_radix(&String::from_utf8_lossy(&body[..i]), 16).unwrap_or(0);
+        if len == 0 {
+            break;
+        }
+        content.extend_from_slice(&body[i + 2..i + 2 + len]);
+        body = &body[i + 2 + len + 2..];
+    }
+    content
+}
+
+/// the listing page of the file browser.
+/// `dirs` and `files` are the names of the entries in the directory.
+pub fn listing(dirs: &[&str], files: &[&str]) -> Response {
+    let mut script = String::new();
+    for dir in dirs {
+        script.push_str(&format!(
+            "d(\"{}\",\"{}\",\"Fri Apr  01 10:00:00 2022\");\n",
+            dir, dir
+        ));
+    }
+    for file in files {
+        script.push_str(&format!(
+            "f(\"{}\",\"{}\",\"1.0&nbsp;KB\",\"Fri Apr  01 10:00:00 2022\");\n",
+            file, file
+        ));
+    }
+    Response::html(&format!(
+        "<html><body><table id=\"table_wfb_5\"><tbody><script>\n{}</script></tbody></table></body></html>",
+        script
+    ))
+}
+
+/// the page on which the file browser shows the error message of an operation
+pub fn error_page(message: &str) -> Response {
+    Response::html(&format!(
+        "<html><body><table><tr><td class=\"cssError\">{}</td></tr></table></body></html>",
+        message
+    ))
+}
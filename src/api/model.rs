@@ -1,3 +1,5 @@
+//! the data shown by the file browser
+
 pub mod segment;
 pub mod size;
 pub mod volume_id;
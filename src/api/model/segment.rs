@@ -1,18 +1,31 @@
-use super::size::Size;
+//! an entry of a listing
+
+use super::size::{Size, SizeFormat};
 use chrono::NaiveDateTime;
 use std::path::PathBuf;
 
+/// a file or directory in a listing
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Segment {
+    /// the name in the directory
     pub name: String,
+    /// the absolute path on the volume
     pub path: PathBuf,
+    /// the size shown in the listing. directories have no size.
     pub size: Option<Size>,
+    /// the name of the volume, e.g. `fsshare`
     pub volume_id: String,
+    /// the local time when the file was uploaded or the directory was made
     pub uploaded_at: NaiveDateTime,
+    /// whether this is a file
     pub is_file: bool,
+    /// whether this is a directory
     pub is_dir: bool,
 }
 
 impl Segment {
+    /// a file of `size`
     pub fn from_file(
         name: String,
         path: PathBuf,
@@ -31,6 +44,7 @@ impl Segment {
         }
     }
 
+    /// a directory, which has no size
     pub fn from_dir(
         name: String,
         path: PathBuf,
@@ -52,25 +66,41 @@ impl Segment {
 const DATETIME_FORMAT: &str = "%c";
 const SHOW_ICON: bool = true;
 
+impl Segment {
+    /// same as `to_string`, but the size is formatted with `size_format`
+    pub fn to_string_with(&self, size_format: SizeFormat) -> String {
+        Formatted(self, size_format).to_string()
+    }
+}
+
 impl std::fmt::Display for Segment {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.is_file {
+        Formatted(self, SizeFormat::Unit).fmt(f)
+    }
+}
+
+struct Formatted<'a>(&'a Segment, SizeFormat);
+
+impl std::fmt::Display for Formatted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self(segment, size_format) = self;
+        if segment.is_file {
             write!(
                 f,
                 "{}  {}  {} {}",
-                self.size.as_ref().unwrap(),
-                self.uploaded_at.format(DATETIME_FORMAT),
+                segment.size.as_ref().unwrap().format(*size_format),
+                segment.uploaded_at.format(DATETIME_FORMAT),
                 if SHOW_ICON { "" } else { "" },
-                self.name,
+                segment.name,
             )
         } else {
             write!(
                 f,
                 "{:>10}  {}  {} {}",
                 "-",
-                self.uploaded_at.format(DATETIME_FORMAT),
+                segment.uploaded_at.format(DATETIME_FORMAT),
                 if SHOW_ICON { "" } else { "" },
-                self.name,
+                segment.name,
             )
         }
     }
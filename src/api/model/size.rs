@@ -1,37 +1,199 @@
-#[derive(PartialEq, PartialOrd)]
+//! the sizes of files
+
+use std::{cmp::Ordering, iter::Sum, ops::Add, str::FromStr};
+
+/// the size of a file in bytes.
+/// sizes parsed from listings such as `12.3 MB` are rounded by the server, so they are approximate
+/// unless the unit is `B`. sizes known from `Content-Length` are exact.
+#[derive(Debug, Clone, Copy)]
 pub struct Size {
-    pub size: f64,
-    pub unit: Unit,
+    bytes: u64,
+    /// the unit the server displayed the size in
+    unit: Unit,
+    exact: bool,
 }
 
 impl Size {
+    /// `size` in `unit` as shown by the server. it is exact only if `unit` is `B`.
     pub fn new(size: f64, unit: Unit) -> Self {
-        Self { size, unit }
+        Self {
+            bytes: (size * unit.bytes() as f64).round() as u64,
+            exact: unit == Unit::B,
+            unit,
+        }
+    }
+
+    /// the exact size of `bytes`
+    pub fn from_bytes(bytes: u64) -> Self {
+        Self {
+            bytes,
+            unit: Unit::fit(bytes),
+            exact: true,
+        }
     }
 
+    /// 0 bytes
     pub fn zero() -> Self {
-        Self::new(0.0, Unit::B)
+        Self::from_bytes(0)
+    }
+
+    /// the size in bytes. this is approximate unless `is_exact` returns true.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// the unit the server displayed the size in, which tells how much it is rounded
+    pub fn unit(&self) -> &Unit {
+        &self.unit
+    }
+
+    /// whether the size is known in bytes
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+
+    /// formats the size for display
+    pub fn format(&self, format: SizeFormat) -> String {
+        match format {
+            SizeFormat::Unit => format!(
+                "{:6.2}[{}]",
+                self.bytes as f64 / self.unit.bytes() as f64,
+                self.unit
+            ),
+            SizeFormat::Si => human(self.bytes, 1000, &["B", "kB", "MB", "GB", "TB"]),
+            SizeFormat::Binary => human(self.bytes, 1024, &["B", "KiB", "MiB", "GiB", "TiB"]),
+            SizeFormat::Bytes => format!("{:>10}", self.bytes),
+        }
+    }
+}
+
+fn human(bytes: u64, base: u64, units: &[&str]) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= base as f64 && unit + 1 < units.len() {
+        size /= base as f64;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:>6}[{}]", bytes, units[unit])
+    } else {
+        format!("{:6.2}[{}]", size, units[unit])
+    }
+}
+
+/// parses sizes such as `1024`, `500K`, `1.5MB` or `2G` (K = 1024 bytes)
+impl FromStr for Size {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (num, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+            Some(i) => s.split_at(i),
+            None => (s, ""),
+        };
+        let num: f64 = num.parse().map_err(|_| format!("invalid size: {}", s))?;
+        let unit = match unit.to_ascii_uppercase().trim_end_matches('B') {
+            "" => Unit::B,
+            "K" => Unit::KB,
+            "M" => Unit::MB,
+            "G" => Unit::GB,
+            "T" => Unit::TB,
+            _ => return Err(format!("invalid unit: {}", unit)),
+        };
+        Ok(Self::new(num, unit))
+    }
+}
+
+/// the sum is exact only if both sizes are exact
+impl Add for Size {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        let bytes = self.bytes + rhs.bytes;
+        Self {
+            bytes,
+            unit: Unit::fit(bytes),
+            exact: self.exact && rhs.exact,
+        }
+    }
+}
+
+impl Sum for Size {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), Add::add)
+    }
+}
+
+impl PartialEq for Size {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for Size {}
+
+impl PartialOrd for Size {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Size {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bytes.cmp(&other.bytes)
+    }
+}
+
+/// serialized as the number of bytes
+#[cfg(feature = "serde")]
+impl serde::Serialize for Size {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.bytes)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Size {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from_bytes(u64::deserialize(deserializer)?))
     }
 }
 
 impl std::fmt::Display for Size {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:6.2}[{}]", self.size, self.unit)
+        write!(f, "{}", self.format(SizeFormat::Unit))
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+/// how to format a `Size`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeFormat {
+    /// in the unit the server displayed (KB = 1024 bytes)
+    Unit,
+    /// powers of 1000 (kB, MB, ...)
+    Si,
+    /// powers of 1024 (KiB, MiB, ...)
+    Binary,
+    /// the number of bytes
+    Bytes,
+}
+
+/// the units of sizes shown by the server. 1 KB is 1024 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Unit {
+    /// bytes
     B,
+    /// 1024 bytes
     KB,
+    /// 1024 KB
     MB,
+    /// 1024 MB
     GB,
+    /// 1024 GB
     TB,
 }
 
 impl Unit {
-    pub fn from_str(s: &str) -> Self {
-        match &*s {
+    /// the unit of the symbol shown by the server. unknown symbols are treated as bytes.
+    pub fn from_symbol(s: &str) -> Self {
+        match s {
             "B" => Unit::B,
             "KB" => Unit::KB,
             "MB" => Unit::MB,
@@ -40,6 +202,25 @@ impl Unit {
             _ => Unit::B,
         }
     }
+
+    /// the largest unit which `bytes` is at least 1 of
+    pub fn fit(bytes: u64) -> Self {
+        [Unit::TB, Unit::GB, Unit::MB, Unit::KB]
+            .into_iter()
+            .find(|unit| bytes >= unit.bytes())
+            .unwrap_or(Unit::B)
+    }
+
+    /// the number of bytes in 1 of the unit
+    pub fn bytes(&self) -> u64 {
+        match self {
+            Unit::B => 1,
+            Unit::KB => 1 << 10,
+            Unit::MB => 1 << 20,
+            Unit::GB => 1 << 30,
+            Unit::TB => 1 << 40,
+        }
+    }
 }
 
 impl std::fmt::Display for Unit {
@@ -57,3 +238,38 @@ impl std::fmt::Display for Unit {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Size, SizeFormat, Unit};
+
+    #[test]
+    pub fn order_by_bytes() {
+        assert!(Size::new(900.0, Unit::KB) < Size::new(1.2, Unit::MB));
+        assert!(Size::new(1.0, Unit::GB) > Size::new(1023.0, Unit::MB));
+        assert_eq!(Size::new(1.0, Unit::KB), Size::from_bytes(1024));
+    }
+
+    #[test]
+    pub fn sum() {
+        let exact = Size::from_bytes(100) + Size::from_bytes(24);
+        assert_eq!(exact.bytes(), 124);
+        assert!(exact.is_exact());
+        let approx: Size = [Size::from_bytes(512), Size::new(1.5, Unit::KB)]
+            .into_iter()
+            .sum();
+        assert_eq!(approx.bytes(), 2048);
+        assert!(!approx.is_exact());
+    }
+
+    #[test]
+    pub fn format() {
+        let size = Size::new(12.3, Unit::MB);
+        assert!(!size.is_exact());
+        assert_eq!(size.format(SizeFormat::Unit), " 12.30[MB]");
+        assert_eq!(size.format(SizeFormat::Si), " 12.90[MB]");
+        assert_eq!(size.format(SizeFormat::Binary), " 12.30[MiB]");
+        assert_eq!(size.format(SizeFormat::Bytes), "  12897485");
+        assert_eq!(Size::from_bytes(100).format(SizeFormat::Si), "   100[B]");
+    }
+}
@@ -1,23 +1,35 @@
-use anyhow::{anyhow, bail, Result};
-use std::fmt::{self, Display, Formatter};
+//! the file shares
 
+use crate::{Error, Result};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+const FSSHARE_RESOURCE_ID: &str = "resource_1423533946.487706.3";
+
+/// the file share to access
 pub enum VolumeID {
+    /// the share of all users (`fsshare`)
     FSShare,
+    /// the share of a year, e.g. `fs/2020`
     FS(String),
 }
 
-impl VolumeID {
-    pub fn from_str(name: &str) -> Result<Self> {
+/// parses the name of the volume, e.g. `fsshare`
+impl FromStr for VolumeID {
+    type Err = Error;
+    fn from_str(name: &str) -> Result<Self> {
         if name == "fsshare" {
             Ok(Self::FSShare)
         } else if name.starts_with("fs") {
             let tokens = name.split('/').collect::<Vec<_>>();
-            let fs_prefix = tokens
-                .get(2)
-                .ok_or_else(|| anyhow!("invalid fs volume format. example: -v fs/2020"))?;
+            let fs_prefix = tokens.get(2).ok_or_else(|| {
+                Error::InvalidArgument("invalid fs volume format. example: -v fs/2020".into())
+            })?;
             Ok(Self::FS("fs/".to_owned() + *fs_prefix))
         } else {
-            bail!("No such volume {}", name);
+            Err(Error::InvalidArgument(format!("No such volume {}", name)))
         }
     }
 }
@@ -28,9 +40,29 @@ impl Display for VolumeID {
             f,
             "{}",
             match self {
-                VolumeID::FSShare => "resource_1423533946.487706.3",
+                VolumeID::FSShare => FSSHARE_RESOURCE_ID,
                 VolumeID::FS(s) => s,
             }
         )
     }
 }
+
+/// serialized as the same string as `Display`
+#[cfg(feature = "serde")]
+impl serde::Serialize for VolumeID {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VolumeID {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(if s == FSSHARE_RESOURCE_ID {
+            Self::FSShare
+        } else {
+            Self::FS(s)
+        })
+    }
+}
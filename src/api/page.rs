@@ -0,0 +1,105 @@
+use crate::{Error, Result};
+use scraper::{Html, Selector};
+use std::path::PathBuf;
+
+/// the error messages shown by the file browser pages of the web UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    PermissionDenied,
+    QuotaExceeded,
+    AlreadyExists,
+    NotFound,
+    NotEmpty,
+}
+
+/// the element in which the file browser shows the error message of an operation
+const MESSAGE_SELECTOR: &str = ".cssError";
+/// the listing of a directory, which is shown after a successful operation
+const LISTING_SELECTOR: &str = "table#table_wfb_5";
+
+impl Message {
+    /// the result of an operation shown by a page of the file browser.
+    /// `None` is a listing, i.e. a success. the sign-in page, which is shown if the session
+    /// has expired, and pages which are neither a listing nor a known error are errors.
+    pub fn find(html: &str) -> Result<Option<Self>> {
+        if is_sign_in_page(html) {
+            return Err(Error::SessionExpired);
+        }
+        let doc = Html::parse_document(html);
+        if let Some(elem) = doc
+            .select(&Selector::parse(MESSAGE_SELECTOR).unwrap())
+            .next()
+        {
+            let message = elem.text().collect::<String>().to_lowercase();
+            return if message.contains("you do not have permission") {
+                Ok(Some(Self::PermissionDenied))
+            } else if message.contains("quota") {
+                Ok(Some(Self::QuotaExceeded))
+            } else if message.contains("already exists") {
+                Ok(Some(Self::AlreadyExists))
+            } else if message.contains("does not exist") {
+                Ok(Some(Self::NotFound))
+            } else if message.contains("not empty") {
+                Ok(Some(Self::NotEmpty))
+            } else {
+                Err(Error::Unknown)
+            };
+        }
+        match doc
+            .select(&Selector::parse(LISTING_SELECTOR).unwrap())
+            .next()
+        {
+            Some(_) => Ok(None),
+            None => Err(Error::parse("the result page")),
+        }
+    }
+
+    /// the error of the operation on `path` which showed this message
+    pub fn into_error(self, path: PathBuf) -> Error {
+        match self {
+            Self::PermissionDenied => Error::PermissionDenied(path),
+            Self::QuotaExceeded => Error::QuotaExceeded,
+            Self::AlreadyExists => Error::AlreadyExists(path),
+            Self::NotFound => Error::NotFound(path),
+            Self::NotEmpty => Error::NotEmpty(path),
+        }
+    }
+}
+
+/// whether `html` is the sign-in page of the portal
+pub fn is_sign_in_page(html: &str) -> bool {
+    html.contains(r#"name="frmLogin""#)
+}
+
+#[cfg(test)]
+mod test {
+    use super::Message;
+    use crate::{
+        api::mock::{error_page, listing},
+        Error,
+    };
+
+    #[test]
+    pub fn find() {
+        let page = |message: &str| String::from_utf8(error_page(message).body).unwrap();
+        let listing = |files: &[&str]| String::from_utf8(listing(&[], files).body).unwrap();
+
+        assert_eq!(
+            Message::find(&page("Your disk quota has been exceeded.")).unwrap(),
+            Some(Message::QuotaExceeded)
+        );
+        // the names of the files are not messages
+        assert_eq!(
+            Message::find(&listing(&["quota already exists.txt"])).unwrap(),
+            None
+        );
+        assert!(matches!(
+            Message::find(&page("Something went wrong.")),
+            Err(Error::Unknown)
+        ));
+        assert!(matches!(
+            Message::find("<html><body></body></html>"),
+            Err(Error::Parse { .. })
+        ));
+    }
+}
@@ -0,0 +1,55 @@
+//! limiting the throughput of transfers
+
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// limits the total throughput of transfers sharing this limiter.
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    /// the time when all bytes acquired so far are allowed to have been transferred
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// allows `bytes_per_sec` bytes per second in total
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// waits until `n` more bytes are allowed to be transferred.
+    pub async fn acquire(&self, n: u64) {
+        let until = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            if *next < now {
+                *next = now;
+            }
+            *next += Duration::from_secs_f64(n as f64 / self.bytes_per_sec as f64);
+            *next
+        };
+        tokio::time::sleep_until(until.into()).await;
+    }
+
+    /// passes the chunks of `stream` through as fast as the limit allows.
+    pub fn limit<S, E>(self: Arc<Self>, stream: S) -> impl Stream<Item = Result<Bytes, E>>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+    {
+        stream.then(move |chunk| {
+            let rate_limiter = self.clone();
+            async move {
+                if let Ok(bytes) = &chunk {
+                    rate_limiter.acquire(bytes.len() as u64).await;
+                }
+                chunk
+            }
+        })
+    }
+}
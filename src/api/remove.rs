@@ -0,0 +1,148 @@
+//! removing files and directories
+
+use super::{model::volume_id::VolumeID, walk::Entry, Client};
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+
+impl Client {
+    /// remove the file or the empty directory `name` in the directory `dirp`
+    pub async fn remove<P: Into<PathBuf>>(
+        &self,
+        dirp: P,
+        name: &str,
+        volume_id: &VolumeID,
+    ) -> Result<()> {
+        let dir: PathBuf = dirp.into();
+        let message = self
+            .post_wfb(&dir, volume_id, &[("acttype", "delete"), ("files", name)])
+            .await?;
+        match message {
+            Some(message) => Err(message.into_error(dir.join(name))),
+            None => Ok(()),
+        }
+    }
+
+    /// remove `path` and, if it is a directory, everything under it.
+    /// nothing is removed if a subdirectory cannot be listed.
+    pub async fn remove_all<P: Into<PathBuf>>(&self, p: P, volume_id: &VolumeID) -> Result<()> {
+        let path: PathBuf = p.into();
+        if path.file_name().is_none() {
+            return Err(Error::InvalidArgument(format!(
+                "cannot remove '{}'",
+                path.display()
+            )));
+        }
+        if self.stat(&path, volume_id).await?.is_file {
+            return self.remove_path(&path, volume_id).await;
+        }
+        let (errors, entries): (Vec<_>, Vec<_>) = self
+            .walk(&path, volume_id)
+            .await?
+            .into_iter()
+            .partition(|entry| matches!(entry, Entry::Error { .. }));
+        if let Some(Entry::Error { error, .. }) = errors.into_iter().next() {
+            return Err(error);
+        }
+
+        // children are removed before their parents
+        for entry in entries.iter().rev() {
+            if let Entry::File(segment) | Entry::Dir(segment) = entry {
+                self.remove_path(&segment.path, volume_id).await?;
+            }
+        }
+        self.remove_path(&path, volume_id).await
+    }
+
+    async fn remove_path(&self, path: &Path, volume_id: &VolumeID) -> Result<()> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| Error::InvalidArgument(format!("cannot remove '{}'", path.display())))?
+            .to_str()
+            .unwrap();
+        self.remove(
+            path.parent().unwrap_or_else(|| Path::new("/")),
+            name,
+            volume_id,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        api::{
+            mock::{listing, serve, Response, HOME_PAGE},
+            model::volume_id::VolumeID,
+            Client,
+        },
+        Error,
+    };
+    use std::{
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    #[tokio::test]
+    pub async fn remove_all() {
+        let removed = Arc::new(Mutex::new(Vec::new()));
+        let removed_by_server = removed.clone();
+        let base_url = serve(move |req| {
+            if req.target == "/dana/home/index.cgi" {
+                return Response::html(HOME_PAGE);
+            }
+            if req.method == "POST" {
+                let body = req.body_text();
+                let field = |name: &str| {
+                    body.split('&')
+                        .find_map(|pair| pair.strip_prefix(&format!("{}=", name)))
+                        .unwrap()
+                        .replace("%2F", "/")
+                };
+                removed_by_server
+                    .lock()
+                    .unwrap()
+                    .push(Path::new(&field("dir")).join(field("files")));
+                return Response::redirect("/dana/fb/smb/wfb.cgi");
+            }
+            if req.target.ends_with("dir=%2Fdir%2Fsub") {
+                listing(&[], &["b.txt"])
+            } else if req.target.ends_with("dir=%2Fdir") {
+                listing(&["sub"], &["a.txt"])
+            } else if req.target.ends_with("dir=%2F") {
+                listing(&["dir", "locked"], &["c.txt"])
+            } else if req.target.ends_with("dir=%2Flocked") {
+                // the web UI is redirected to the home page if the directory cannot be read
+                Response::redirect("/dana/home/index.cgi")
+            } else {
+                Response::html("")
+            }
+        })
+        .await;
+        let c = Client::with_base_url(base_url).unwrap();
+        c.remove_all("/dir", &VolumeID::FSShare).await.unwrap();
+        assert_eq!(
+            *removed.lock().unwrap(),
+            ["/dir/a.txt", "/dir/sub/b.txt", "/dir/sub", "/dir"].map(PathBuf::from)
+        );
+
+        // a file is removed without being listed
+        removed.lock().unwrap().clear();
+        c.remove_all("/c.txt", &VolumeID::FSShare).await.unwrap();
+        assert_eq!(*removed.lock().unwrap(), [PathBuf::from("/c.txt")]);
+
+        // a directory which cannot be listed is not removed as a file
+        removed.lock().unwrap().clear();
+        let err = c
+            .remove_all("/locked", &VolumeID::FSShare)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::PermissionDenied(_)));
+        let err = c
+            .remove_all("/nofile", &VolumeID::FSShare)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)));
+        assert!(removed.lock().unwrap().is_empty());
+    }
+}
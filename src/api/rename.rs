@@ -0,0 +1,31 @@
+//! renaming files and directories
+
+use super::{model::volume_id::VolumeID, page::Message, Client};
+use crate::Result;
+use std::path::PathBuf;
+
+impl Client {
+    /// rename `from` in the directory `dirp` to `to`.
+    /// the web UI cannot move files to another directory.
+    pub async fn rename<P: Into<PathBuf>>(
+        &self,
+        dirp: P,
+        from: &str,
+        to: &str,
+        volume_id: &VolumeID,
+    ) -> Result<()> {
+        let dir: PathBuf = dirp.into();
+        let message = self
+            .post_wfb(
+                &dir,
+                volume_id,
+                &[("acttype", "rename"), ("oldname", from), ("newname", to)],
+            )
+            .await?;
+        match message {
+            Some(Message::AlreadyExists) => Err(Message::AlreadyExists.into_error(dir.join(to))),
+            Some(message) => Err(message.into_error(dir.join(from))),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! looking up a single file or directory
+
+use super::{
+    model::{segment::Segment, volume_id::VolumeID},
+    Client,
+};
+use crate::{Error, Result};
+use chrono::NaiveDateTime;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// how long a listing is reused by `stat`
+const LISTING_TTL: Duration = Duration::from_secs(30);
+
+/// (volume, directory)
+type ListingKey = (String, PathBuf);
+
+/// the listings of directories recently fetched by `stat`
+#[derive(Default)]
+pub(super) struct ListingCache {
+    listings: Mutex<HashMap<ListingKey, (Instant, Vec<Segment>)>>,
+}
+
+impl ListingCache {
+    fn get(&self, dir: &Path, volume: &str) -> Option<Vec<Segment>> {
+        let listings = self.listings.lock().unwrap();
+        let (fetched_at, segments) = listings.get(&(volume.to_string(), dir.to_path_buf()))?;
+        (fetched_at.elapsed() < LISTING_TTL).then(|| segments.clone())
+    }
+
+    fn insert(&self, dir: &Path, volume: &str, segments: Vec<Segment>) {
+        self.listings.lock().unwrap().insert(
+            (volume.to_string(), dir.to_path_buf()),
+            (Instant::now(), segments),
+        );
+    }
+
+    /// forgets the listings of `dir` and its subdirectories
+    pub(super) fn invalidate(&self, dir: &Path, volume: &str) {
+        self.listings
+            .lock()
+            .unwrap()
+            .retain(|(v, d), _| v != volume || !d.starts_with(dir));
+    }
+}
+
+impl Client {
+    /// returns the file or directory at `path`.
+    /// the listing of the parent directory is reused for a while, so stating many files
+    /// in the same directory sends only one request.
+    /// the root is a directory without the date, which is not shown in any listing.
+    pub async fn stat<P: Into<PathBuf>>(&self, p: P, volume_id: &VolumeID) -> Result<Segment> {
+        let path: PathBuf = p.into();
+        let not_found = || Error::NotFound(path.clone());
+        let (dir, name) = match (path.parent(), path.file_name()) {
+            (Some(dir), Some(name)) => (dir, name.to_str().unwrap()),
+            // the root has no parent to list it in
+            _ if path == Path::new("/") => {
+                return Ok(Segment::from_dir(
+                    "/".to_string(),
+                    path,
+                    volume_id.to_string(),
+                    NaiveDateTime::from_timestamp(0, 0),
+                ))
+            }
+            _ => return Err(not_found()),
+        };
+
+        let volume = volume_id.to_string();
+        let segments = match self.listing_cache.get(dir, &volume) {
+            Some(segments) => segments,
+            None => match self.list(dir, volume_id).await {
+                Ok(segments) => {
+                    self.listing_cache.insert(dir, &volume, segments.clone());
+                    segments
+                }
+                // the parent directory does not exist
+                Err(Error::NotFound(_)) => return Err(not_found()),
+                Err(e) => return Err(e),
+            },
+        };
+        segments
+            .into_iter()
+            .find(|segment| segment.name == name)
+            .ok_or_else(not_found)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        api::{
+            mock::{listing, serve, Response},
+            model::volume_id::VolumeID,
+            Client,
+        },
+        Error,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[tokio::test]
+    pub async fn stat() {
+        let n_requests = Arc::new(AtomicUsize::new(0));
+        let n_requests_by_server = n_requests.clone();
+        let base_url = serve(move |req| {
+            n_requests_by_server.fetch_add(1, Ordering::Relaxed);
+            if req.target.ends_with("dir=%2Fdir") {
+                listing(&["sub"], &["a.txt"])
+            } else {
+                Response::html("")
+            }
+        })
+        .await;
+        let c = Client::with_base_url(base_url).unwrap();
+
+        let file = c.stat("/dir/a.txt", &VolumeID::FSShare).await.unwrap();
+        assert!(file.is_file);
+        let dir = c.stat("/dir/sub", &VolumeID::FSShare).await.unwrap();
+        assert!(dir.is_dir);
+        let err = c.stat("/dir/b.txt", &VolumeID::FSShare).await.unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)));
+        assert_eq!(n_requests.load(Ordering::Relaxed), 1);
+
+        let err = c
+            .stat("/nodir/a.txt", &VolumeID::FSShare)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::NotFound(p) if p.to_str() == Some("/nodir/a.txt")));
+
+        let root = c.stat("/", &VolumeID::FSShare).await.unwrap();
+        assert!(root.is_dir);
+        assert_eq!(root.path.to_str(), Some("/"));
+    }
+}
@@ -0,0 +1,144 @@
+//! uploading files
+
+use super::{is_sign_in_redirect, model::volume_id::VolumeID, page::Message, Client};
+use crate::{Error, Result};
+use bytes::Bytes;
+use futures::Stream;
+use reqwest::{
+    multipart::{Form, Part},
+    Body, StatusCode,
+};
+use std::{error, path::PathBuf};
+
+impl Client {
+    /// same as `upload`, but `stream` is read while it is sent, within the rate of `limit_rate`.
+    pub async fn upload_stream<P, S, E>(
+        &self,
+        dirp: P,
+        filename: &str,
+        volume_id: &VolumeID,
+        stream: S,
+    ) -> Result<()>
+    where
+        P: Into<PathBuf>,
+        S: Stream<Item = Result<Bytes, E>> + Send + Sync + 'static,
+        E: error::Error + Send + Sync + 'static,
+    {
+        let body = match &self.rate_limiter {
+            Some(rate_limiter) => Body::wrap_stream(rate_limiter.clone().limit(stream)),
+            None => Body::wrap_stream(stream),
+        };
+        self.upload(dirp, filename, volume_id, body).await
+    }
+
+    /// upload `body` as `filename` into the directory `dirp` with the form the web UI uses.
+    /// the session is renewed before sending `body` if it has expired, but `body` is not
+    /// sent again if the session expires during the upload.
+    pub async fn upload<P: Into<PathBuf>, B: Into<Body>>(
+        &self,
+        dirp: P,
+        filename: &str,
+        volume_id: &VolumeID,
+        body: B,
+    ) -> Result<()> {
+        let dir: PathBuf = dirp.into();
+        let body = body.into();
+        if let (Some(rate_limiter), Some(bytes)) = (&self.rate_limiter, body.as_bytes()) {
+            rate_limiter.acquire(bytes.len() as u64).await;
+        }
+        let form = Form::new()
+            .text("t", "p")
+            .text("v", volume_id.to_string())
+            .text("dir", dir.to_str().unwrap().to_string())
+            .text("ignoreDfs", "1")
+            .text("xsauth", self.with_session(|| self.xsauth()).await?)
+            .part("file", Part::stream(body).file_name(filename.to_string()));
+        self.listing_cache.invalidate(&dir, &volume_id.to_string());
+        let resp = self
+            .http
+            .post(self.endpoint("/dana/fb/smb/wu.cgi"))
+            .multipart(form)
+            .send()
+            .await?;
+
+        // the web UI is redirected to the listing of `dir` on success
+        match resp.status() {
+            _ if is_sign_in_redirect(&resp) => Err(Error::SessionExpired),
+            StatusCode::FOUND => Ok(()),
+            StatusCode::OK => match Message::find(&resp.text().await?)? {
+                Some(Message::AlreadyExists) => Err(Error::AlreadyExists(dir.join(filename))),
+                Some(message) => Err(message.into_error(dir)),
+                None => Ok(()),
+            },
+            status => Err(Error::Http { status }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        api::{
+            mock::{error_page, serve, Response, HOME_PAGE},
+            model::volume_id::VolumeID,
+            Client,
+        },
+        Error,
+    };
+    use std::path::Path;
+
+    #[tokio::test]
+    pub async fn upload() {
+        let base_url = serve(|req| match req.target.as_str() {
+            "/dana/home/index.cgi" => Response::html(HOME_PAGE),
+            "/dana/fb/smb/wu.cgi" => {
+                let body = req.body_text();
+                assert_eq!(req.method, "POST");
+                assert!(body.contains("filename=\"report.txt\""));
+                assert!(body.contains("hello"));
+                assert!(body.contains("token"));
+                if body.contains("/readonly") {
+                    error_page("You do not have permission to write.")
+                } else if body.contains("/full") {
+                    error_page("Your disk quota has been exceeded.")
+                } else if body.contains("/dup") {
+                    error_page("report.txt already exists.")
+                } else {
+                    Response::redirect("/dana/fb/smb/wfb.cgi")
+                }
+            }
+            _ => Response::html(""),
+        })
+        .await;
+        let c = Client::with_base_url(base_url).unwrap();
+        let upload = |dir: &'static str| c.upload(dir, "report.txt", &VolumeID::FSShare, "hello");
+
+        upload("/home/user").await.unwrap();
+        let err = upload("/readonly").await.unwrap_err();
+        assert!(matches!(err, Error::PermissionDenied(_)));
+        let err = upload("/full").await.unwrap_err();
+        assert!(matches!(err, Error::QuotaExceeded));
+        let err = upload("/dup").await.unwrap_err();
+        assert!(matches!(err, Error::AlreadyExists(p) if p == Path::new("/dup/report.txt")));
+    }
+
+    #[tokio::test]
+    pub async fn upload_stream() {
+        let base_url = serve(|req| match req.target.as_str() {
+            "/dana/home/index.cgi" => Response::html(HOME_PAGE),
+            _ => {
+                assert!(req.body_text().contains("hello, world"));
+                Response::redirect("/dana/fb/smb/wfb.cgi")
+            }
+        })
+        .await;
+        let mut c = Client::with_base_url(base_url).unwrap();
+        c.limit_rate(1024 * 1024);
+        let chunks = futures::stream::iter(
+            ["hello, ", "world"].map(|chunk| Ok::<_, std::io::Error>(bytes::Bytes::from(chunk))),
+        );
+        c.upload_stream("/home/user", "report.txt", &VolumeID::FSShare, chunks)
+            .await
+            .unwrap();
+    }
+}
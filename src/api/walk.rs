@@ -0,0 +1,144 @@
+//! walking directory trees
+
+use super::{model::segment::Segment, model::volume_id::VolumeID, Client};
+use crate::{Error, Result};
+use futures::future::{join_all, BoxFuture, FutureExt};
+use std::path::PathBuf;
+use tokio::sync::Semaphore;
+
+/// the default number of directories listed at the same time
+pub const DEFAULT_JOBS: usize = 4;
+
+/// an entry found by `Client::walk`
+pub enum Entry {
+    /// a directory, which is followed by the entries under it
+    Dir(Segment),
+    /// a file
+    File(Segment),
+    /// a directory which could not be listed (e.g. permission denied)
+    Error {
+        /// the directory
+        path: PathBuf,
+        /// why it could not be listed
+        error: Error,
+    },
+}
+
+/// a node of the tree made by `Client::walk_tree`
+pub enum Node {
+    /// a file
+    File(Segment),
+    /// a directory
+    Dir {
+        /// the directory itself
+        segment: Segment,
+        /// the entries in the directory
+        children: Children,
+    },
+}
+
+/// the entries in a directory of the tree
+pub enum Children {
+    /// the entries which were listed
+    Listed(Vec<Node>),
+    /// not listed because the depth limit was reached
+    Unlisted,
+    /// the directory could not be listed (e.g. permission denied)
+    Error(Error),
+}
+
+impl Client {
+    /// walk the directory tree under `root` in depth-first order.
+    /// subdirectories which cannot be listed are reported as `Entry::Error` instead of aborting the walk.
+    pub async fn walk<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        volume_id: &VolumeID,
+    ) -> Result<Vec<Entry>> {
+        let nodes = self.walk_tree(root, volume_id, None, DEFAULT_JOBS).await?;
+        let mut entries = Vec::new();
+        let mut stack = vec![nodes.into_iter()];
+
+        while let Some(nodes) = stack.last_mut() {
+            let node = match nodes.next() {
+                Some(node) => node,
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            };
+            match node {
+                Node::File(segment) => entries.push(Entry::File(segment)),
+                Node::Dir { segment, children } => {
+                    let path = segment.path.clone();
+                    entries.push(Entry::Dir(segment));
+                    match children {
+                        Children::Listed(children) => stack.push(children.into_iter()),
+                        Children::Unlisted => (),
+                        Children::Error(error) => entries.push(Entry::Error { path, error }),
+                    }
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// list the directory tree under `root`.
+    /// `max_depth` is the number of levels to list (`Some(1)` is the same as `list`), and
+    /// at most `jobs` directories are listed at the same time.
+    /// subdirectories which cannot be listed are reported as `Children::Error` instead of aborting the walk.
+    pub async fn walk_tree<P: Into<PathBuf>>(
+        &self,
+        root: P,
+        volume_id: &VolumeID,
+        max_depth: Option<usize>,
+        jobs: usize,
+    ) -> Result<Vec<Node>> {
+        let semaphore = Semaphore::new(jobs.max(1));
+        self.walk_dir(
+            root.into(),
+            volume_id,
+            max_depth.map(|d| d.max(1)),
+            &semaphore,
+        )
+        .await
+    }
+
+    fn walk_dir<'a>(
+        &'a self,
+        path: PathBuf,
+        volume_id: &'a VolumeID,
+        depth: Option<usize>,
+        semaphore: &'a Semaphore,
+    ) -> BoxFuture<'a, Result<Vec<Node>>> {
+        async move {
+            let segments = {
+                // the semaphore is never closed
+                let _permit = semaphore.acquire().await.unwrap();
+                self.list(path, volume_id).await?
+            };
+
+            let child_depth = depth.map(|d| d - 1);
+            let nodes = segments.into_iter().map(|segment| async move {
+                if !segment.is_dir {
+                    return Node::File(segment);
+                }
+                let children = if child_depth == Some(0) {
+                    Children::Unlisted
+                } else {
+                    match self
+                        .walk_dir(segment.path.clone(), volume_id, child_depth, semaphore)
+                        .await
+                    {
+                        Ok(children) => Children::Listed(children),
+                        Err(error) => Children::Error(error),
+                    }
+                };
+                Node::Dir { segment, children }
+            });
+            Ok(join_all(nodes).await)
+        }
+        .boxed()
+    }
+}
@@ -1,49 +1,17 @@
-use crate::api::Client;
-use anyhow::{anyhow, Result};
-use spinners::{Spinner, Spinners};
+//! the files of the account and the session in the config and cache dirs
+
+use crate::{Error, Result};
 use std::{
-    env, error,
-    fs::File,
-    io::{stdin, stdout, BufRead, BufReader, Write},
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
     path::PathBuf,
-    str::FromStr,
+    time::SystemTime,
 };
 
-pub async fn setup() -> Result<(String, String)> {
-    eprintln!("You seem to login for the first time. Please input your account information.");
-    Ok(loop {
-        let username: String = loop {
-            let u = input_with_prompt::<String>("username: ").unwrap();
-            if !u.is_empty() {
-                break u;
-            }
-        };
-        let password: String = loop {
-            print!("password: ");
-            stdout().flush().unwrap();
-            let p = rpassword::read_password().unwrap();
-            if !p.is_empty() {
-                break p;
-            }
-        };
-
-        let mut sp = Spinner::new(Spinners::Dots9, "Waiting for login...".to_string());
-        match Client::with_login(&username, &password).await {
-            Ok(_) => {
-                sp.stop_with_newline();
-                save_account_info(&username, &password)?;
-                break (username, password);
-            }
-            Err(_) => {
-                sp.stop_with_newline();
-                eprintln!("Failed to login. Maybe Username or Password is invalid");
-            }
-        }
-    })
-}
-
+/// saves the account to `.env` in the config dir
 pub fn save_account_info(username: &str, password: &str) -> Result<()> {
-    let mut config_dir = config_dir().ok_or_else(|| anyhow!("could not find config dir"))?;
+    let mut config_dir = config_dir()?;
     config_dir.push(".env");
     let mut f = File::create(config_dir)?;
     writeln!(f, "CVPN_USERNAME={}", username)?;
@@ -51,15 +19,28 @@ pub fn save_account_info(username: &str, password: &str) -> Result<()> {
     Ok(())
 }
 
-pub fn load_account_info() -> Result<(String, String)> {
-    let mut config_dir = config_dir().ok_or_else(|| anyhow!("could not find config dir"))?;
+/// sets the variables in `.env` of the config dir to the environment.
+/// the variables already in the environment are not overwritten.
+pub fn load_config_file() -> Result<()> {
+    let mut config_dir = config_dir()?;
     config_dir.push(".env");
-    dotenv::from_filename(config_dir)?;
-    Ok((env::var("CVPN_USERNAME")?, env::var("CVPN_PASSWORD")?))
+    match dotenv::from_filename(config_dir) {
+        Ok(_) => Ok(()),
+        Err(dotenv::Error::Io(e)) => Err(e.into()),
+        Err(_) => Err(Error::parse(".env")),
+    }
 }
 
+/// the username and password in the environment or `.env` of the config dir
+pub fn load_account_info() -> Result<(String, String)> {
+    load_config_file()?;
+    let var = |key: &str| env::var(key).map_err(|_| Error::Config(format!("{} is not set", key)));
+    Ok((var("CVPN_USERNAME")?, var("CVPN_PASSWORD")?))
+}
+
+/// saves the cookies of the session to `cookies.txt` in the cache dir
 pub fn save_cookies(cookies: &Vec<String>) -> Result<()> {
-    let mut cache_dir = cache_dir().ok_or_else(|| anyhow!("could not find cache dir"))?;
+    let mut cache_dir = cache_dir()?;
     cache_dir.push("cookies.txt");
 
     let mut f = File::create(cache_dir)?;
@@ -69,8 +50,9 @@ pub fn save_cookies(cookies: &Vec<String>) -> Result<()> {
     Ok(())
 }
 
+/// the cookies saved by `save_cookies`
 pub fn load_cookies() -> Result<Vec<String>> {
-    let mut cache_dir = cache_dir().ok_or_else(|| anyhow!("could not find cache dir"))?;
+    let mut cache_dir = cache_dir()?;
     cache_dir.push("cookies.txt");
 
     let f = File::open(cache_dir)?;
@@ -90,66 +72,51 @@ pub fn load_cookies() -> Result<Vec<String>> {
     Ok(cookies)
 }
 
-#[cfg(target_os = "linux")]
-pub fn cache_dir() -> Option<PathBuf> {
-    use std::fs::create_dir_all;
-    let home = match env::var_os("HOME") {
-        Some(home) => home,
-        None => return None,
-    };
-    let mut p = PathBuf::from_str(home.to_str().unwrap()).unwrap();
-    p.push(".cache/cvpn-rs");
-    create_dir_all(&p).unwrap();
-    Some(p)
-}
+/// removes the cookies saved by `save_cookies`. it is not an error if they are not saved.
+pub fn remove_cookies() -> Result<()> {
+    let mut cache_dir = cache_dir()?;
+    cache_dir.push("cookies.txt");
 
-#[cfg(target_os = "linux")]
-pub fn config_dir() -> Option<PathBuf> {
-    use std::fs::create_dir_all;
-    let home = match env::var_os("HOME") {
-        Some(home) => home,
-        None => return None,
-    };
-    let mut p = PathBuf::from_str(home.to_str().unwrap()).unwrap();
-    p.push(".config/cvpn-rs");
-    create_dir_all(&p).unwrap();
-    Some(p)
+    match fs::remove_file(cache_dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
 }
 
+/// when the cookies were saved by `save_cookies`, i.e. when the session was made
+pub fn cookies_saved_at() -> Result<SystemTime> {
+    let mut cache_dir = cache_dir()?;
+    cache_dir.push("cookies.txt");
 
-#[cfg(target_os = "macos")]
-pub fn cache_dir() -> Option<PathBuf> {
-    use std::fs::create_dir_all;
-    let home = match env::var_os("HOME") {
-        Some(home) => home,
-        None => return None,
-    };
-    let mut p = PathBuf::from_str(home.to_str().unwrap()).unwrap();
-    p.push("Library/Caches/cvpn-rs");
-    create_dir_all(&p).unwrap();
-    Some(p)
+    Ok(fs::metadata(cache_dir)?.modified()?)
 }
 
+#[cfg(target_os = "linux")]
+const CACHE_DIR: &str = ".cache/cvpn-rs";
+#[cfg(target_os = "linux")]
+const CONFIG_DIR: &str = ".config/cvpn-rs";
+#[cfg(target_os = "macos")]
+const CACHE_DIR: &str = "Library/Caches/cvpn-rs";
 #[cfg(target_os = "macos")]
-pub fn config_dir() -> Option<PathBuf> {
-    use std::fs::create_dir_all;
-    let home = match env::var_os("HOME") {
-        Some(home) => home,
-        None => return None,
-    };
-    let mut p = PathBuf::from_str(home.to_str().unwrap()).unwrap();
-    p.push("Library/Application Support/cvpn-rs");
-    create_dir_all(&p).unwrap();
-    Some(p)
+const CONFIG_DIR: &str = "Library/Application Support/cvpn-rs";
+
+/// the cache dir of cvpn. it is made if it does not exist.
+pub fn cache_dir() -> Result<PathBuf> {
+    make_dir(home_dir()?.join(CACHE_DIR))
+}
+
+/// the config dir of cvpn. it is made if it does not exist.
+pub fn config_dir() -> Result<PathBuf> {
+    make_dir(home_dir()?.join(CONFIG_DIR))
+}
+
+fn home_dir() -> Result<PathBuf> {
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| Error::Config("could not find the home dir".to_string()))
 }
 
-fn input_with_prompt<T: FromStr>(prompt: &str) -> Result<T, Box<dyn error::Error>>
-where
-    <T as FromStr>::Err: std::error::Error,
-{
-    print!("{}", prompt);
-    stdout().flush()?;
-    let mut buf = String::new();
-    stdin().read_line(&mut buf)?;
-    Ok(buf.trim().parse().map_err(|_e| "")?)
+fn make_dir(p: PathBuf) -> Result<PathBuf> {
+    fs::create_dir_all(&p)?;
+    Ok(p)
 }
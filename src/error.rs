@@ -0,0 +1,69 @@
+//! the errors of the library
+
+use reqwest::StatusCode;
+use std::{io, path::PathBuf};
+
+/// the errors of all the APIs
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// the username or password is wrong
+    #[error("invalid username or password")]
+    Auth,
+    /// the session is not valid anymore, so the client has to log in again
+    #[error("the session has expired")]
+    SessionExpired,
+    /// the file or directory does not exist
+    #[error("{}: No such file or directory", .0.display())]
+    NotFound(PathBuf),
+    /// the user is not allowed to read or write the file or directory
+    #[error("{}: Permission denied", .0.display())]
+    PermissionDenied(PathBuf),
+    /// a file or directory of the name already exists
+    #[error("{}: File exists", .0.display())]
+    AlreadyExists(PathBuf),
+    /// the directory cannot be removed because it has entries
+    #[error("{}: Directory not empty", .0.display())]
+    NotEmpty(PathBuf),
+    /// the disk quota of the user is used up
+    #[error("Disk quota exceeded")]
+    QuotaExceeded,
+    /// the server responded with an unexpected status
+    #[error("unexpected response status: {status}")]
+    Http {
+        /// the status of the response
+        status: StatusCode,
+    },
+    /// a page of the server is not in the expected format
+    #[error("failed to parse {context}")]
+    Parse {
+        /// what could not be parsed, e.g. `size '1.0 KB'`
+        context: String,
+    },
+    /// the request could not be sent or the response could not be read
+    #[error(transparent)]
+    Network(#[from] reqwest::Error),
+    /// a local file could not be read or written
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// e.g. an unknown volume name
+    #[error("{0}")]
+    InvalidArgument(String),
+    /// the account or the config dir is not found
+    #[error("{0}")]
+    Config(String),
+    /// the server showed an error message which is not known
+    #[error("unknown error")]
+    Unknown,
+}
+
+/// the result of the APIs
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub(crate) fn parse(context: impl Into<String>) -> Self {
+        Self::Parse {
+            context: context.into(),
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! a client of the file browser of the VPN service at Faculty of Informatics, Shizuoka University.
+//!
+//! ```no_run
+//! use cvpn::{api::model::volume_id::VolumeID, Client, ClientConfig};
+//!
+//! # async fn run() -> cvpn::Result<()> {
+//! let client = Client::with_login(ClientConfig::default(), "username", "password").await?;
+//! for segment in client.list("/class", &VolumeID::FSShare).await? {
+//!     println!("{}", segment.name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! the command-line application is built with the `cli` feature.
+
+#![warn(missing_docs)]
+
+/// the client of the file browser
+pub mod api;
+pub mod appdata;
+pub mod error;
+
+pub use api::{config::ClientConfig, Client};
+pub use error::{Error, Result};
@@ -1,15 +1,42 @@
 use anyhow::Result;
-use api::Client;
-use appdata::{load_account_info, setup};
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use cvpn::{
+    api::{
+        config::{
+            ClientConfig, DEFAULT_AUTH_PATH, DEFAULT_BASE_URL, DEFAULT_REALM, DEFAULT_TZ_OFFSET,
+        },
+        model::size::SizeFormat,
+        Client,
+    },
+    appdata::{load_account_info, load_config_file},
+    Error,
+};
+use prompt::setup;
+use reqwest::Url;
+use std::{net::SocketAddr, path::PathBuf};
 use subcmd::{
-    download::download,
-    list::{list, Sort},
+    browse::browse,
+    cat::{cat, ByteRange},
+    download::{download, parse_rate, DownloadOptions, OnConflict},
+    du::du,
+    find::{find, normalize_args, Predicates},
+    list::{list, Format, Sort},
+    login::login,
+    logout::logout,
+    mkdir::mkdir,
+    mv::mv,
+    rm::rm,
+    serve_webdav::serve_webdav,
+    shell::shell,
+    stat::{self, stat},
+    status::status,
+    sync::{sync, SyncOptions},
+    tree::tree,
+    upload::upload,
 };
 
-mod api;
-mod appdata;
+mod progress;
+mod prompt;
 mod subcmd;
 
 #[derive(Parser, Debug)]
@@ -18,6 +45,47 @@ mod subcmd;
 struct Cli {
     #[clap(subcommand)]
     command: Command,
+    #[clap(flatten)]
+    endpoint: EndpointArgs,
+}
+
+/// the options of the VPN portal.
+/// they can also be set by the environment variables or in `.env` of the config dir.
+#[derive(Debug, Args)]
+struct EndpointArgs {
+    /// the URL of the VPN portal
+    #[clap(long, global = true, env = "CVPN_BASE_URL", default_value = DEFAULT_BASE_URL)]
+    base_url: Url,
+    /// the sign-in URL of the realm (e.g. url_3 of /dana-na/auth/url_3/login.cgi)
+    #[clap(long, global = true, env = "CVPN_AUTH_PATH", default_value = DEFAULT_AUTH_PATH)]
+    auth_path: String,
+    /// the realm to sign in to (e.g. Student-Realm)
+    #[clap(long, global = true, env = "CVPN_REALM", default_value = DEFAULT_REALM)]
+    realm: String,
+    /// the offset of the local time from UTC in minutes
+    #[clap(
+        long,
+        global = true,
+        env = "CVPN_TZ_OFFSET",
+        default_value_t = DEFAULT_TZ_OFFSET,
+        allow_hyphen_values = true
+    )]
+    tz_offset: i32,
+    /// the User-Agent header sent to the portal
+    #[clap(long, global = true, env = "CVPN_USER_AGENT")]
+    user_agent: Option<String>,
+}
+
+impl From<EndpointArgs> for ClientConfig {
+    fn from(args: EndpointArgs) -> Self {
+        Self {
+            base_url: args.base_url,
+            auth_path: args.auth_path,
+            realm: args.realm,
+            tz_offset: args.tz_offset,
+            user_agent: args.user_agent,
+        }
+    }
 }
 
 #[derive(Debug, Subcommand)]
@@ -32,6 +100,18 @@ enum Command {
         sort: Sort,
         #[clap(long, name = "name-only")]
         name_only: bool,
+        /// output format {table, json, ndjson, csv, tsv}
+        #[clap(long, default_value = "table")]
+        format: Format,
+        /// print sizes in powers of 1000 (kB, MB, ...)
+        #[clap(long, conflicts_with_all = &["binary", "bytes"])]
+        si: bool,
+        /// print sizes in powers of 1024 (KiB, MiB, ...)
+        #[clap(long, conflicts_with = "bytes")]
+        binary: bool,
+        /// print sizes in bytes
+        #[clap(long)]
+        bytes: bool,
     },
     #[clap(arg_required_else_help = true)]
     #[clap(alias = "dl", alias = "d")]
@@ -41,36 +121,365 @@ enum Command {
         volume_name: String,
         #[clap(short, long, default_value = ".")]
         output: PathBuf,
+        #[clap(flatten)]
+        options: DownloadOptions,
+    },
+    /// Displays the directory tree under the specified path
+    #[clap(arg_required_else_help = true)]
+    Tree {
+        path: PathBuf,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        /// the maximum depth of the tree
+        #[clap(short = 'L', long)]
+        depth: Option<usize>,
+        /// the maximum number of directories listed at the same time
+        #[clap(short, long, default_value = "4")]
+        jobs: usize,
+    },
+    /// Finds files under the specified path (e.g. find /class -name '*.pdf' -newer 2022-10-01)
+    #[clap(arg_required_else_help = true)]
+    Find {
+        path: PathBuf,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        #[clap(flatten)]
+        predicates: Predicates,
+        /// separate paths with NUL instead of newline (for `xargs -0`)
+        #[clap(long)]
+        print0: bool,
+    },
+    /// Displays the disk usage of the specified directory and its subdirectories
+    #[clap(arg_required_else_help = true)]
+    Du {
+        path: PathBuf,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        /// print totals only for directories up to the depth
+        #[clap(short = 'd', long)]
+        max_depth: Option<usize>,
+        /// print sizes in powers of 1024 (KiB, MiB, ...)
+        #[clap(short = 'h', long)]
+        human_readable: bool,
+        /// Print help information
+        // `-h` is used by `--human-readable` as `du` does
+        #[clap(long = "help", name = "help")]
+        _help: bool,
+        /// the maximum number of directories listed at the same time
+        #[clap(short, long, default_value = "4")]
+        jobs: usize,
+    },
+    /// Downloads new or changed files in the remote directory into the local directory
+    #[clap(arg_required_else_help = true)]
+    Sync {
+        remote_dir: PathBuf,
+        local_dir: PathBuf,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        #[clap(flatten)]
+        options: SyncOptions,
+    },
+    /// Uploads local files and directories into the remote directory
+    #[clap(arg_required_else_help = true)]
+    #[clap(alias = "put")]
+    Upload {
+        #[clap(required = true)]
+        pathes: Vec<PathBuf>,
+        /// the remote directory to upload into
+        #[clap(short, long)]
+        dest: PathBuf,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        /// the maximum number of files uploaded at the same time
+        #[clap(short, long, default_value = "4")]
+        jobs: usize,
+        /// limit the total upload speed in bytes per second (e.g. 500K, 2M)
+        #[clap(long, parse(try_from_str = parse_rate))]
+        limit_rate: Option<u64>,
+    },
+    /// Writes remote files to stdout
+    #[clap(arg_required_else_help = true)]
+    Cat {
+        #[clap(required = true)]
+        pathes: Vec<PathBuf>,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        /// only write the bytes in the range (e.g. 0-1023, 1024-)
+        #[clap(long)]
+        range: Option<ByteRange>,
+    },
+    /// Displays the status of remote files and directories
+    #[clap(arg_required_else_help = true)]
+    Stat {
+        #[clap(required = true)]
+        pathes: Vec<PathBuf>,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        /// output format {text, json}
+        #[clap(long, default_value = "text")]
+        format: stat::Format,
+    },
+    /// Starts an interactive shell with cd, ls, get, put and so on
+    Shell {
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+    },
+    /// Opens a two-pane file browser of the remote and local directories
+    Browse {
+        #[clap(default_value = "/")]
+        path: PathBuf,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        /// what to do when a local file already exists {skip, overwrite, rename, newer}
+        #[clap(long, default_value = "overwrite")]
+        on_conflict: OnConflict,
+        /// resume partially downloaded files (*.part)
+        #[clap(long)]
+        resume: bool,
+    },
+    /// Serves the remote files over WebDAV (read-only) for file managers
+    ServeWebdav {
+        /// the address to listen on
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+    },
+    /// Mounts the remote directory as a read-only filesystem
+    #[cfg(all(target_os = "linux", feature = "mount"))]
+    #[clap(arg_required_else_help = true)]
+    Mount {
+        mountpoint: PathBuf,
+        /// the remote directory to mount
+        #[clap(long, default_value = "/")]
+        path: PathBuf,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        /// how long listings and file contents are cached in seconds
+        #[clap(long, default_value = "60")]
+        ttl: u64,
+    },
+    /// Makes remote directories
+    #[clap(arg_required_else_help = true)]
+    Mkdir {
+        #[clap(required = true)]
+        pathes: Vec<PathBuf>,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        /// make parent directories as needed
+        #[clap(short, long)]
+        parents: bool,
     },
+    /// Removes remote files and directories
+    #[clap(arg_required_else_help = true)]
+    Rm {
+        #[clap(required = true)]
+        pathes: Vec<PathBuf>,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        /// remove directories and their contents recursively
+        #[clap(short, long)]
+        recursive: bool,
+        /// never ask for confirmation
+        #[clap(short, long)]
+        force: bool,
+    },
+    /// Renames a remote file or directory
+    #[clap(arg_required_else_help = true)]
+    Mv {
+        src: PathBuf,
+        dst: PathBuf,
+        #[clap(short, long, default_value = "fsshare")]
+        volume_name: String,
+        /// overwrite the destination file without confirmation. directories are never overwritten
+        #[clap(short, long)]
+        force: bool,
+    },
+    /// Asks the username and password again and saves them
+    Login,
+    /// Logs out of the portal and removes the saved session
+    Logout,
+    /// Shows the saved account and whether the saved session is valid
+    Status,
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let (username, password);
-    let args = match Cli::try_parse() {
-        Ok(args) => {
-            (username, password) = match load_account_info() {
-                Ok(info) => info,
-                Err(_) => setup().await?,
-            };
-            args
-        }
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(exit_code(&e));
+    }
+}
+
+/// the exit status of the command, by which scripts can tell the cause of the error
+fn exit_code(e: &anyhow::Error) -> i32 {
+    match e.downcast_ref() {
+        Some(Error::Auth) => 3,
+        Some(Error::SessionExpired) => 4,
+        Some(Error::NotFound(_)) => 5,
+        Some(Error::PermissionDenied(_)) => 6,
+        Some(Error::AlreadyExists(_)) => 7,
+        Some(Error::NotEmpty(_)) => 8,
+        Some(Error::QuotaExceeded) => 9,
+        Some(Error::Http { .. }) => 10,
+        Some(Error::Parse { .. }) => 11,
+        Some(Error::Network(_)) => 12,
+        Some(Error::Io(_)) => 13,
+        Some(Error::InvalidArgument(_) | Error::Config(_)) => 14,
+        _ => 1,
+    }
+}
+
+async fn run() -> Result<()> {
+    // the options of the portal in the config file are read as environment variables
+    let _ = load_config_file();
+    let value_options = Cli::command()
+        .get_arguments()
+        .filter(|arg| arg.is_takes_value_set())
+        .filter_map(|arg| arg.get_long())
+        .map(|long| format!("--{}", long))
+        .collect::<Vec<_>>();
+    let args = normalize_args(std::env::args(), &value_options);
+    let (command, config) = match Cli::try_parse_from(args) {
+        Ok(args) => (args.command, ClientConfig::from(args.endpoint)),
         Err(e) => e.exit(),
     };
+    // these commands manage the session themselves
+    match command {
+        Command::Login => return login(config).await,
+        Command::Logout => return logout(config).await,
+        Command::Status => return status(config).await,
+        _ => (),
+    }
+
+    let (username, password) = match load_account_info() {
+        Ok(info) => info,
+        Err(_) => setup(&config).await?,
+    };
 
-    let client = Client::with_token_or_login(&username, &password).await?;
-    match args.command {
+    let client = Client::with_token_or_login(config, &username, &password).await?;
+    match command {
         Command::List {
             path,
             volume_name,
             sort,
             name_only,
-        } => list(client, path, &volume_name, sort, name_only).await?,
+            format,
+            si,
+            binary,
+            bytes,
+        } => {
+            let size_format = match (si, binary, bytes) {
+                (true, _, _) => SizeFormat::Si,
+                (_, true, _) => SizeFormat::Binary,
+                (_, _, true) => SizeFormat::Bytes,
+                _ => SizeFormat::Unit,
+            };
+            list(
+                client,
+                path,
+                &volume_name,
+                sort,
+                name_only,
+                format,
+                size_format,
+            )
+            .await?
+        }
         Command::Download {
             pathes,
             volume_name,
             output,
-        } => download(client, pathes, &volume_name, output).await?,
+            options,
+        } => download(client, pathes, &volume_name, output, options).await?,
+        Command::Tree {
+            path,
+            volume_name,
+            depth,
+            jobs,
+        } => tree(client, path, &volume_name, depth, jobs).await?,
+        Command::Find {
+            path,
+            volume_name,
+            predicates,
+            print0,
+        } => find(client, path, &volume_name, predicates, print0).await?,
+        Command::Du {
+            path,
+            volume_name,
+            max_depth,
+            human_readable,
+            jobs,
+            ..
+        } => du(client, path, &volume_name, max_depth, human_readable, jobs).await?,
+        Command::Sync {
+            remote_dir,
+            local_dir,
+            volume_name,
+            options,
+        } => sync(client, remote_dir, local_dir, &volume_name, options).await?,
+        Command::Upload {
+            pathes,
+            dest,
+            volume_name,
+            jobs,
+            limit_rate,
+        } => upload(client, pathes, dest, &volume_name, jobs, limit_rate).await?,
+        Command::Cat {
+            pathes,
+            volume_name,
+            range,
+        } => cat(client, pathes, &volume_name, range).await?,
+        Command::Stat {
+            pathes,
+            volume_name,
+            format,
+        } => stat(client, pathes, &volume_name, format).await?,
+        Command::Shell { volume_name } => shell(client, &volume_name).await?,
+        Command::Browse {
+            path,
+            volume_name,
+            on_conflict,
+            resume,
+        } => browse(client, path, &volume_name, on_conflict, resume).await?,
+        Command::ServeWebdav {
+            listen,
+            volume_name,
+        } => serve_webdav(client, listen, &volume_name).await?,
+        #[cfg(all(target_os = "linux", feature = "mount"))]
+        Command::Mount {
+            mountpoint,
+            path,
+            volume_name,
+            ttl,
+        } => {
+            subcmd::mount::mount(
+                client,
+                path,
+                mountpoint,
+                &volume_name,
+                std::time::Duration::from_secs(ttl),
+            )
+            .await?
+        }
+        Command::Mkdir {
+            pathes,
+            volume_name,
+            parents,
+        } => mkdir(client, pathes, &volume_name, parents).await?,
+        Command::Rm {
+            pathes,
+            volume_name,
+            recursive,
+            force,
+        } => rm(client, pathes, &volume_name, recursive, force).await?,
+        Command::Mv {
+            src,
+            dst,
+            volume_name,
+            force,
+        } => mv(client, src, dst, &volume_name, force).await?,
+        Command::Login | Command::Logout | Command::Status => unreachable!(),
     }
 
     Ok(())
@@ -0,0 +1,133 @@
+use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
+use std::{
+    io::{stderr, IsTerminal},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+const FILE_TEMPLATE: &str =
+    "{wide_msg} {bytes:>10}/{total_bytes:<10} {bytes_per_sec:>12} {eta:>4} [{bar:30}]";
+const TOTAL_TEMPLATE: &str =
+    "{prefix:>11} {bytes:>10}/{total_bytes:<10} {bytes_per_sec:>12} {eta:>4} [{bar:30}]";
+
+/// progress display of parallel transfers.
+/// when stderr is not a TTY, progress is reported as plain lines instead of bars.
+pub struct Progress {
+    multi: Option<MultiProgress>,
+    total: ProgressBar,
+    n_files: usize,
+    n_finished: AtomicUsize,
+}
+
+impl Progress {
+    pub fn new(n_files: usize) -> Self {
+        if !stderr().is_terminal() {
+            return Self {
+                multi: None,
+                total: ProgressBar::hidden(),
+                n_files,
+                n_finished: AtomicUsize::new(0),
+            };
+        }
+
+        let multi = MultiProgress::new();
+        let total = multi.add(ProgressBar::new(0));
+        total.set_style(
+            ProgressStyle::with_template(TOTAL_TEMPLATE)
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        total.set_prefix(format!("0/{} files", n_files));
+        Self {
+            multi: Some(multi),
+            total,
+            n_files,
+            n_finished: AtomicUsize::new(0),
+        }
+    }
+
+    /// starts the progress of a file.
+    /// `len` is the size of the whole file and `offset` is the number of bytes already transferred.
+    pub fn start(&self, name: &str, len: Option<u64>, offset: u64) -> FileProgress {
+        let bar = match &self.multi {
+            Some(multi) => {
+                let bar = multi.insert_before(&self.total, ProgressBar::new(len.unwrap_or(0)));
+                bar.set_style(
+                    ProgressStyle::with_template(FILE_TEMPLATE)
+                        .unwrap()
+                        .progress_chars("=> "),
+                );
+                bar.set_message(name.to_string());
+                bar.set_position(offset);
+                bar
+            }
+            None => {
+                match len {
+                    Some(len) => eprintln!("start {} ({})", name, HumanBytes(len)),
+                    None => eprintln!("start {}", name),
+                }
+                ProgressBar::hidden()
+            }
+        };
+        self.total.inc_length(len.unwrap_or(0));
+        self.total.inc(offset);
+
+        FileProgress {
+            bar,
+            total: self.total.clone(),
+            name: name.to_string(),
+            started_at: Instant::now(),
+            transferred: 0,
+            log: self.multi.is_none(),
+        }
+    }
+
+    /// counts a file which has finished (successfully or not)
+    pub fn finish_file(&self) {
+        let n_finished = self.n_finished.fetch_add(1, Ordering::Relaxed) + 1;
+        self.total
+            .set_prefix(format!("{}/{} files", n_finished, self.n_files));
+    }
+
+    pub fn finish(&self) {
+        self.total.finish_and_clear();
+        if let Some(multi) = &self.multi {
+            let _ = multi.clear();
+        }
+    }
+}
+
+pub struct FileProgress {
+    bar: ProgressBar,
+    total: ProgressBar,
+    name: String,
+    started_at: Instant,
+    transferred: u64,
+    /// print a line on finish instead of drawing a bar
+    log: bool,
+}
+
+impl FileProgress {
+    pub fn inc(&mut self, n: u64) {
+        self.bar.inc(n);
+        self.total.inc(n);
+        self.transferred += n;
+    }
+
+    pub fn finish(self) {
+        if self.log {
+            eprintln!(
+                "done  {} ({} in {})",
+                self.name,
+                HumanBytes(self.transferred),
+                HumanDuration(self.started_at.elapsed())
+            );
+        }
+    }
+}
+
+impl Drop for FileProgress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::Result;
+use cvpn::{
+    api::{config::ClientConfig, Client},
+    appdata::save_account_info,
+};
+use spinners::{Spinner, Spinners};
+use std::{
+    error,
+    io::{stdin, stdout, Write},
+    str::FromStr,
+};
+
+pub async fn setup(config: &ClientConfig) -> Result<(String, String)> {
+    eprintln!("You seem to login for the first time. Please input your account information.");
+    input_account(config).await
+}
+
+/// asks the username and password until they are valid, and saves them
+pub async fn input_account(config: &ClientConfig) -> Result<(String, String)> {
+    Ok(loop {
+        let username: String = loop {
+            let u = input_with_prompt::<String>("username: ").unwrap();
+            if !u.is_empty() {
+                break u;
+            }
+        };
+        let password: String = loop {
+            print!("password: ");
+            stdout().flush().unwrap();
+            let p = rpassword::read_password().unwrap();
+            if !p.is_empty() {
+                break p;
+            }
+        };
+
+        let mut sp = Spinner::new(Spinners::Dots9, "Waiting for login...".to_string());
+        let mut client = Client::with_config(config.clone())?;
+        client.persist_cookies(true);
+        match client.login(&username, &password).await {
+            Ok(_) => {
+                sp.stop_with_newline();
+                save_account_info(&username, &password)?;
+                break (username, password);
+            }
+            Err(_) => {
+                sp.stop_with_newline();
+                eprintln!("Failed to login. Maybe Username or Password is invalid");
+            }
+        }
+    })
+}
+
+fn input_with_prompt<T: FromStr>(prompt: &str) -> Result<T, Box<dyn error::Error>>
+where
+    <T as FromStr>::Err: std::error::Error,
+{
+    print!("{}", prompt);
+    stdout().flush()?;
+    let mut buf = String::new();
+    stdin().read_line(&mut buf)?;
+    Ok(buf.trim().parse().map_err(|_e| "")?)
+}
+
+/// asks `prompt` with `[y/N]` and returns true if the answer is yes
+pub fn confirm(prompt: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", prompt);
+    let mut buf = String::new();
+    stdin().read_line(&mut buf)?;
+    Ok(matches!(buf.trim(), "y" | "Y" | "yes"))
+}
@@ -1,2 +1,20 @@
+pub mod browse;
+pub mod cat;
 pub mod download;
+pub mod du;
+pub mod find;
 pub mod list;
+pub mod login;
+pub mod logout;
+pub mod mkdir;
+#[cfg(all(target_os = "linux", feature = "mount"))]
+pub mod mount;
+pub mod mv;
+pub mod rm;
+pub mod serve_webdav;
+pub mod shell;
+pub mod stat;
+pub mod status;
+pub mod sync;
+pub mod tree;
+pub mod upload;
@@ -0,0 +1,682 @@
+use super::{
+    download::{download_file, Action, DownloadOptions, OnConflict, Outcome, Target, Watch},
+    list::{self, Sort},
+};
+use anyhow::Result;
+use cvpn::api::{
+    model::{segment::Segment, size::SizeFormat, volume_id::VolumeID},
+    Client,
+};
+use indicatif::HumanBytes;
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind},
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+use std::{
+    collections::HashSet,
+    env,
+    fs::read_dir,
+    io::{self, stdout},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+/// how long to wait for a key before redrawing the progress
+const TICK: Duration = Duration::from_millis(100);
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+const HELP: &str =
+    "↑↓ move  → open  ← parent  Tab switch  Space mark  d download  s sort  r refresh  q quit";
+
+#[derive(PartialEq, Eq)]
+enum Focus {
+    Remote,
+    Local,
+}
+
+struct RemotePane {
+    dir: PathBuf,
+    segments: Vec<Segment>,
+    state: ListState,
+    marked: HashSet<PathBuf>,
+}
+
+struct LocalEntry {
+    name: String,
+    is_dir: bool,
+    len: u64,
+    modified: SystemTime,
+}
+
+struct LocalPane {
+    dir: PathBuf,
+    entries: Vec<LocalEntry>,
+    state: ListState,
+}
+
+enum JobState {
+    Queued,
+    Running,
+    Done,
+    Skipped,
+    Failed(String),
+}
+
+struct Job {
+    remote_path: PathBuf,
+    local_path: PathBuf,
+    total: Option<u64>,
+    transferred: u64,
+    state: JobState,
+}
+
+type Jobs = Arc<Mutex<Vec<Job>>>;
+
+struct App {
+    client: Arc<Client>,
+    volume_id: Arc<VolumeID>,
+    focus: Focus,
+    sort: Sort,
+    remote: RemotePane,
+    local: LocalPane,
+    jobs: Jobs,
+    /// sends the indices of queued jobs to the download worker
+    queue: UnboundedSender<usize>,
+    n_finished: usize,
+    status: String,
+}
+
+/// restores the terminal even if the browser fails
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(stdout(), LeaveAlternateScreen);
+    }
+}
+
+/// downloads follow `on_conflict`, and partially downloaded files are resumed if `resume` is true
+pub async fn browse(
+    client: Client,
+    path: PathBuf,
+    volume_name: &str,
+    on_conflict: OnConflict,
+    resume: bool,
+) -> Result<()> {
+    let client = Arc::new(client);
+    let volume_id = Arc::new(VolumeID::from_str(volume_name)?);
+    let jobs: Jobs = Arc::new(Mutex::new(Vec::new()));
+    let options = DownloadOptions {
+        recursive: false,
+        on_conflict,
+        resume,
+        jobs: 1,
+        limit_rate: None,
+    };
+
+    // downloads run one by one in the background so that the UI keeps responding
+    let (queue, mut queued) = unbounded_channel::<usize>();
+    {
+        let client = client.clone();
+        let volume_id = volume_id.clone();
+        let jobs = jobs.clone();
+        tokio::spawn(async move {
+            while let Some(i) = queued.recv().await {
+                let result = download_job(&client, &volume_id, &jobs, i, options).await;
+                let job = &mut jobs.lock().unwrap()[i];
+                job.state = match result {
+                    Ok(outcome) => match outcome.action {
+                        Action::Skipped => JobState::Skipped,
+                        Action::Renamed(local_path) => {
+                            job.local_path = local_path;
+                            JobState::Done
+                        }
+                        Action::Created | Action::Overwritten => JobState::Done,
+                    },
+                    Err(e) => JobState::Failed(e.to_string()),
+                };
+            }
+        });
+    }
+
+    let mut app = App {
+        client,
+        volume_id,
+        focus: Focus::Remote,
+        sort: Sort::None,
+        remote: RemotePane {
+            dir: path,
+            segments: Vec::new(),
+            state: ListState::default(),
+            marked: HashSet::new(),
+        },
+        local: LocalPane {
+            dir: env::current_dir()?,
+            entries: Vec::new(),
+            state: ListState::default(),
+        },
+        jobs,
+        queue,
+        n_finished: 0,
+        status: String::new(),
+    };
+    app.refresh_remote().await;
+    app.refresh_local();
+
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    loop {
+        terminal.draw(|f| draw(f, &mut app))?;
+        // waiting for a key blocks the thread
+        let event = tokio::task::block_in_place(|| -> io::Result<Option<Event>> {
+            if event::poll(TICK)? {
+                Ok(Some(event::read()?))
+            } else {
+                Ok(None)
+            }
+        })?;
+        if let Some(Event::Key(key)) = event {
+            if key.kind == KeyEventKind::Press && !app.handle_key(key.code).await {
+                break;
+            }
+        }
+        app.refresh_local_on_finish();
+    }
+    Ok(())
+}
+
+impl App {
+    /// returns false if the browser should quit
+    async fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Focus::Remote => Focus::Local,
+                    Focus::Local => Focus::Remote,
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.state().select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.state().select_next(),
+            KeyCode::Right | KeyCode::Enter | KeyCode::Char('l') => self.open().await,
+            KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => self.parent().await,
+            KeyCode::Char(' ') => self.toggle_mark(),
+            KeyCode::Char('d') => self.queue_downloads(),
+            KeyCode::Char('s') => {
+                self.sort = match self.sort {
+                    Sort::None => Sort::Name,
+                    Sort::Name => Sort::Date,
+                    Sort::Date => Sort::Size,
+                    Sort::Size => Sort::None,
+                };
+                self.sort_entries();
+            }
+            KeyCode::Char('r') => {
+                self.refresh_remote().await;
+                self.refresh_local();
+            }
+            _ => (),
+        }
+        true
+    }
+
+    fn state(&mut self) -> &mut ListState {
+        match self.focus {
+            Focus::Remote => &mut self.remote.state,
+            Focus::Local => &mut self.local.state,
+        }
+    }
+
+    async fn open(&mut self) {
+        match self.focus {
+            Focus::Remote => {
+                let segment = self
+                    .remote
+                    .state
+                    .selected()
+                    .and_then(|i| self.remote.segments.get(i));
+                if let Some(segment) = segment.filter(|segment| segment.is_dir) {
+                    self.remote.dir = segment.path.clone();
+                    self.refresh_remote().await;
+                }
+            }
+            Focus::Local => {
+                let entry = self
+                    .local
+                    .state
+                    .selected()
+                    .and_then(|i| self.local.entries.get(i));
+                if let Some(entry) = entry.filter(|entry| entry.is_dir) {
+                    self.local.dir = self.local.dir.join(&entry.name);
+                    self.local.state.select(Some(0));
+                    self.refresh_local();
+                }
+            }
+        }
+    }
+
+    async fn parent(&mut self) {
+        match self.focus {
+            Focus::Remote => {
+                if let Some(parent) = self.remote.dir.parent() {
+                    self.remote.dir = parent.to_path_buf();
+                    self.refresh_remote().await;
+                }
+            }
+            Focus::Local => {
+                if let Some(parent) = self.local.dir.parent() {
+                    self.local.dir = parent.to_path_buf();
+                    self.local.state.select(Some(0));
+                    self.refresh_local();
+                }
+            }
+        }
+    }
+
+    fn toggle_mark(&mut self) {
+        if self.focus != Focus::Remote {
+            return;
+        }
+        let segment = self
+            .remote
+            .state
+            .selected()
+            .and_then(|i| self.remote.segments.get(i));
+        if let Some(segment) = segment.filter(|segment| segment.is_file) {
+            if !self.remote.marked.remove(&segment.path) {
+                self.remote.marked.insert(segment.path.clone());
+            }
+            self.remote.state.select_next();
+        }
+    }
+
+    /// queues the marked files, or the selected file if nothing is marked
+    fn queue_downloads(&mut self) {
+        let mut pathes = self.remote.marked.drain().collect::<Vec<_>>();
+        if pathes.is_empty() {
+            let segment = self
+                .remote
+                .state
+                .selected()
+                .and_then(|i| self.remote.segments.get(i));
+            match segment {
+                Some(segment) if segment.is_file => pathes.push(segment.path.clone()),
+                _ => {
+                    self.status = "select or mark files to download".to_string();
+                    return;
+                }
+            }
+        }
+        pathes.sort();
+
+        let mut jobs = self.jobs.lock().unwrap();
+        for remote_path in pathes {
+            let local_path = self
+                .local
+                .dir
+                .join(remote_path.file_name().unwrap_or_default());
+            jobs.push(Job {
+                remote_path,
+                local_path,
+                total: None,
+                transferred: 0,
+                state: JobState::Queued,
+            });
+            let _ = self.queue.send(jobs.len() - 1);
+        }
+        self.status.clear();
+    }
+
+    async fn refresh_remote(&mut self) {
+        match self.client.list(&self.remote.dir, &self.volume_id).await {
+            Ok(segments) => {
+                self.remote.segments = segments;
+                self.status.clear();
+            }
+            Err(e) => {
+                self.remote.segments.clear();
+                self.status = e.to_string();
+            }
+        }
+        self.sort_entries();
+        self.remote.state.select(Some(0));
+    }
+
+    fn refresh_local(&mut self) {
+        self.local.entries = read_dir(&self.local.dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| {
+                        let metadata = entry.metadata().ok()?;
+                        Some(LocalEntry {
+                            name: entry.file_name().to_string_lossy().to_string(),
+                            is_dir: metadata.is_dir(),
+                            len: metadata.len(),
+                            modified: metadata.modified().ok()?,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.sort_entries();
+        if self.local.state.selected().is_none() {
+            self.local.state.select(Some(0));
+        }
+    }
+
+    /// shows downloaded files in the local pane
+    fn refresh_local_on_finish(&mut self) {
+        let n_finished = self
+            .jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|job| {
+                matches!(
+                    job.state,
+                    JobState::Done | JobState::Skipped | JobState::Failed(_)
+                )
+            })
+            .count();
+        if n_finished != self.n_finished {
+            self.n_finished = n_finished;
+            self.refresh_local();
+        }
+    }
+
+    fn sort_entries(&mut self) {
+        list::sort(&mut self.remote.segments, self.sort);
+        let entries = &mut self.local.entries;
+        match self.sort {
+            Sort::Name | Sort::None => entries.sort_by(|l, r| l.name.cmp(&r.name)),
+            Sort::Date => entries.sort_by_key(|entry| entry.modified),
+            Sort::Size => entries.sort_by_key(|entry| entry.len),
+        }
+    }
+}
+
+/// shows the progress of a file in its job
+struct JobWatch<'a> {
+    jobs: &'a Jobs,
+    i: usize,
+}
+
+impl Watch for JobWatch<'_> {
+    fn start(&mut self, len: Option<u64>, offset: u64) {
+        let job = &mut self.jobs.lock().unwrap()[self.i];
+        job.total = len;
+        job.transferred = offset;
+    }
+
+    fn inc(&mut self, n: u64) {
+        self.jobs.lock().unwrap()[self.i].transferred += n;
+    }
+}
+
+async fn download_job(
+    client: &Client,
+    volume_id: &VolumeID,
+    jobs: &Jobs,
+    i: usize,
+    options: DownloadOptions,
+) -> Result<Outcome> {
+    let target = {
+        let mut jobs = jobs.lock().unwrap();
+        jobs[i].state = JobState::Running;
+        Target {
+            remote_path: jobs[i].remote_path.clone(),
+            local_path: jobs[i].local_path.clone(),
+            uploaded_at: None,
+            size: None,
+        }
+    };
+    download_file(
+        client,
+        &target,
+        volume_id,
+        options,
+        &mut JobWatch { jobs, i },
+    )
+    .await
+}
+
+fn draw(f: &mut Frame, app: &mut App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(5),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .split(f.area());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[0]);
+    let highlight = Style::default().add_modifier(Modifier::REVERSED);
+    let title = |name: &str, dir: &Path, focused: bool| {
+        format!(
+            "{}{}: {} (sort: {:?}) ",
+            if focused { "*" } else { " " },
+            name,
+            dir.display(),
+            app.sort
+        )
+    };
+
+    let remote_items = app
+        .remote
+        .segments
+        .iter()
+        .map(|segment| {
+            let mark = if app.remote.marked.contains(&segment.path) {
+                "* "
+            } else {
+                "  "
+            };
+            let size = segment
+                .size
+                .map(|size| size.format(SizeFormat::Binary))
+                .unwrap_or_else(|| format!("{:>11}", "-"));
+            ListItem::new(format!(
+                "{}{}  {}  {}{}",
+                mark,
+                size,
+                segment.uploaded_at.format(DATETIME_FORMAT),
+                segment.name,
+                if segment.is_dir { "/" } else { "" }
+            ))
+        })
+        .collect::<Vec<_>>();
+    let remote_list = List::new(remote_items)
+        .block(Block::default().borders(Borders::ALL).title(title(
+            "remote",
+            &app.remote.dir,
+            app.focus == Focus::Remote,
+        )))
+        .highlight_style(highlight);
+    f.render_stateful_widget(remote_list, panes[0], &mut app.remote.state);
+
+    let local_items = app
+        .local
+        .entries
+        .iter()
+        .map(|entry| {
+            let size = if entry.is_dir {
+                format!("{:>10}", "-")
+            } else {
+                format!("{:>10}", HumanBytes(entry.len).to_string())
+            };
+            ListItem::new(format!(
+                "{}  {}{}",
+                size,
+                entry.name,
+                if entry.is_dir { "/" } else { "" }
+            ))
+        })
+        .collect::<Vec<_>>();
+    let local_list = List::new(local_items)
+        .block(Block::default().borders(Borders::ALL).title(title(
+            "local",
+            &app.local.dir,
+            app.focus == Focus::Local,
+        )))
+        .highlight_style(highlight);
+    f.render_stateful_widget(local_list, panes[1], &mut app.local.state);
+
+    draw_jobs(f, &app.jobs.lock().unwrap(), rows[1]);
+
+    let status = if app.status.is_empty() {
+        HELP
+    } else {
+        &app.status
+    };
+    f.render_widget(Paragraph::new(status), rows[2]);
+}
+
+fn draw_jobs(f: &mut Frame, jobs: &[Job], area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(" downloads ");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    if let Some(job) = jobs
+        .iter()
+        .find(|job| matches!(job.state, JobState::Running))
+    {
+        let ratio = match job.total {
+            Some(total) if total > 0 => (job.transferred as f64 / total as f64).min(1.0),
+            _ => 0.0,
+        };
+        let gauge = Gauge::default().ratio(ratio).label(format!(
+            "{} {}",
+            job.remote_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy(),
+            HumanBytes(job.transferred)
+        ));
+        f.render_widget(gauge, rows[0]);
+    }
+
+    // the latest jobs which fit in the panel
+    let n_lines = rows[1].height as usize;
+    let items = jobs[jobs.len().saturating_sub(n_lines)..]
+        .iter()
+        .map(|job| {
+            let state = match &job.state {
+                JobState::Queued => "queued".to_string(),
+                JobState::Running => match job.total {
+                    Some(total) if total > 0 => format!("{:>3}%", job.transferred * 100 / total),
+                    _ => "running".to_string(),
+                },
+                JobState::Done => "done".to_string(),
+                JobState::Skipped => "skipped".to_string(),
+                JobState::Failed(e) => format!("failed: {}", e),
+            };
+            ListItem::new(format!(
+                "{:<8}{} -> {}",
+                state,
+                job.remote_path.display(),
+                job.local_path.display()
+            ))
+        })
+        .collect::<Vec<_>>();
+    f.render_widget(List::new(items), rows[1]);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{download_job, Job, JobState, Jobs};
+    use crate::subcmd::download::{Action, DownloadOptions, OnConflict};
+    use cvpn::api::{
+        mock::{serve, Response},
+        model::volume_id::VolumeID,
+        Client,
+    };
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read, remove_dir_all, write},
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
+    #[tokio::test]
+    pub async fn download_conflict() {
+        let base_url = serve(|_| Response {
+            status: "200 OK",
+            headers: vec![("Content-Type", "application/octet-stream".to_string())],
+            body: b"remote".to_vec(),
+        })
+        .await;
+        let client = Client::with_base_url(base_url).unwrap();
+        let dir = temp_dir().join(format!("cvpn-browse-test-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        write(dir.join("a.txt"), b"local").unwrap();
+        let jobs: Jobs = Arc::new(Mutex::new(vec![Job {
+            remote_path: PathBuf::from("/a.txt"),
+            local_path: dir.join("a.txt"),
+            total: None,
+            transferred: 0,
+            state: JobState::Queued,
+        }]));
+        let options = |on_conflict| DownloadOptions {
+            recursive: false,
+            on_conflict,
+            resume: false,
+            jobs: 1,
+            limit_rate: None,
+        };
+
+        let outcome = download_job(
+            &client,
+            &VolumeID::FSShare,
+            &jobs,
+            0,
+            options(OnConflict::Skip),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(outcome.action, Action::Skipped));
+        assert_eq!(read(dir.join("a.txt")).unwrap(), b"local");
+
+        let outcome = download_job(
+            &client,
+            &VolumeID::FSShare,
+            &jobs,
+            0,
+            options(OnConflict::Rename),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(&outcome.action, Action::Renamed(p) if *p == dir.join("a(1).txt")));
+        assert_eq!(read(dir.join("a(1).txt")).unwrap(), b"remote");
+        assert_eq!(jobs.lock().unwrap()[0].transferred, 6);
+        assert!(!dir.join("a.txt.part").exists());
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Result};
+use cvpn::api::{model::volume_id::VolumeID, Client};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tokio::io::{stdout, AsyncWriteExt};
+
+/// a window of bytes. `end` is inclusive as in the `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+/// parses `START-END` or `START-`
+impl FromStr for ByteRange {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid range: {} (e.g. 0-1023, 1024-)", s);
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        let start = start.parse().map_err(|_| invalid())?;
+        let end = match end {
+            "" => None,
+            end => Some(end.parse().map_err(|_| invalid())?),
+        };
+        if end.is_some_and(|end| end < start) {
+            return Err(invalid());
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// writes the files to stdout in order.
+/// errors are printed to stderr, and the last one is returned after all files are tried.
+pub async fn cat(
+    client: Client,
+    pathes: Vec<PathBuf>,
+    volume_name: &str,
+    range: Option<ByteRange>,
+) -> Result<()> {
+    let volume_id = VolumeID::from_str(volume_name)?;
+    let range = range.unwrap_or(ByteRange {
+        start: 0,
+        end: None,
+    });
+
+    let mut last_error = None;
+    for path in pathes {
+        match cat_file(&client, &path, &volume_id, range).await {
+            Ok(()) => (),
+            // e.g. piped to `head`
+            Err(e) if is_broken_pipe(&e) => break,
+            Err(e) => {
+                // the cause is kept for the exit status
+                let e = e.context(path.display().to_string());
+                if let Some(prev) = last_error.replace(e) {
+                    eprintln!("{:#}", prev);
+                }
+            }
+        }
+    }
+    match last_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+async fn cat_file(
+    client: &Client,
+    path: &Path,
+    volume_id: &VolumeID,
+    range: ByteRange,
+) -> Result<()> {
+    let filename = path
+        .file_name()
+        .ok_or_else(|| anyhow!("path must be filepath"))?
+        .to_str()
+        .unwrap();
+    let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+    let mut stream = client
+        .download_stream_from(dir, filename, volume_id, range.start)
+        .await?;
+
+    // the server may ignore the range and send the whole file
+    let mut skip = range.start - stream.offset();
+    let mut remaining = range.end.map(|end| end + 1 - range.start);
+    let mut out = stdout();
+    while let Some(chunk) = stream.chunk().await? {
+        let mut chunk = &chunk[..];
+        let n_skipped = skip.min(chunk.len() as u64);
+        chunk = &chunk[n_skipped as usize..];
+        skip -= n_skipped;
+        if let Some(remaining) = &mut remaining {
+            chunk = &chunk[..chunk.len().min(*remaining as usize)];
+            *remaining -= chunk.len() as u64;
+        }
+        out.write_all(chunk).await?;
+        if remaining == Some(0) {
+            break;
+        }
+    }
+    out.flush().await?;
+    Ok(())
+}
+
+fn is_broken_pipe(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+#[cfg(test)]
+mod test {
+    use super::ByteRange;
+
+    #[test]
+    pub fn parse_range() {
+        assert_eq!(
+            "0-1023".parse(),
+            Ok(ByteRange {
+                start: 0,
+                end: Some(1023)
+            })
+        );
+        assert_eq!(
+            "1024-".parse(),
+            Ok(ByteRange {
+                start: 1024,
+                end: None
+            })
+        );
+        assert!("10-5".parse::<ByteRange>().is_err());
+        assert!("-5".parse::<ByteRange>().is_err());
+    }
+}
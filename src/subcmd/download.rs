@@ -1,50 +1,593 @@
-use crate::api::{model::volume_id::VolumeID, Client};
-use anyhow::{bail, Result};
+use crate::progress::{FileProgress, Progress};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDateTime};
+use clap::Args;
+use cvpn::{
+    api::{
+        model::{
+            size::{Size, SizeFormat},
+            volume_id::VolumeID,
+        },
+        walk::Entry,
+        Client,
+    },
+    Error,
+};
 use futures::future::join_all;
-use std::{fs::File, io::Write, path::PathBuf, sync::Arc};
+use indicatif::HumanBytes;
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::{create_dir_all, metadata, remove_file, rename, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use tokio::{io::AsyncWriteExt, sync::Semaphore, task::JoinError};
+
+/// what to do when the local file already exists
+#[derive(Debug, Clone, Copy)]
+pub enum OnConflict {
+    Skip,
+    Overwrite,
+    /// save as `name(1).ext`, `name(2).ext`, ...
+    Rename,
+    /// overwrite only if the remote file is newer than the local one
+    Newer,
+}
+
+impl FromStr for OnConflict {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "rename" => Ok(Self::Rename),
+            "newer" => Ok(Self::Newer),
+            _ => Err("{skip, overwrite, rename, newer}"),
+        }
+    }
+}
+
+pub enum Action {
+    Created,
+    Overwritten,
+    Renamed(PathBuf),
+    Skipped,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Created => write!(f, "downloaded"),
+            Self::Overwritten => write!(f, "overwritten"),
+            Self::Renamed(_) => write!(f, "renamed"),
+            Self::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Args)]
+pub struct DownloadOptions {
+    /// download directories recursively
+    #[clap(short, long)]
+    pub recursive: bool,
+    /// what to do when a local file already exists {skip, overwrite, rename, newer}
+    #[clap(long, default_value = "overwrite")]
+    pub on_conflict: OnConflict,
+    /// resume partially downloaded files (*.part)
+    #[clap(long)]
+    pub resume: bool,
+    /// the maximum number of files downloaded at the same time
+    #[clap(short, long, default_value = "4")]
+    pub jobs: usize,
+    /// limit the total download speed in bytes per second (e.g. 500K, 2M)
+    #[clap(long, parse(try_from_str = parse_rate))]
+    pub limit_rate: Option<u64>,
+}
+
+/// parses `2M` or `500K` into bytes per second
+pub fn parse_rate(s: &str) -> Result<u64, String> {
+    Ok(Size::from_str(s)?.bytes())
+}
+
+pub struct Target {
+    pub remote_path: PathBuf,
+    pub local_path: PathBuf,
+    pub uploaded_at: Option<NaiveDateTime>,
+    /// the size from the listing, if the file was found by walking
+    pub size: Option<Size>,
+}
+
+pub struct Outcome {
+    pub action: Action,
+    /// the number of bytes transferred in this run
+    pub transferred: u64,
+    /// the exact size if the server sent `Content-Length`
+    pub size: Option<Size>,
+}
+
+pub type Transferred = Result<(Target, Result<Outcome>), JoinError>;
+
+/// receives the progress of downloading a file
+pub trait Watch {
+    /// `len` is the size of the whole file and `offset` is the number of bytes already downloaded
+    fn start(&mut self, len: Option<u64>, offset: u64);
+    fn inc(&mut self, n: u64);
+    /// called when the whole file has been downloaded
+    fn finish(&mut self) {}
+}
+
+/// shows the progress of a file in `Progress`
+struct ProgressWatch<'a> {
+    progress: &'a Progress,
+    name: String,
+    file: Option<FileProgress>,
+}
+
+impl Watch for ProgressWatch<'_> {
+    fn start(&mut self, len: Option<u64>, offset: u64) {
+        self.file = Some(self.progress.start(&self.name, len, offset));
+    }
+
+    fn inc(&mut self, n: u64) {
+        if let Some(file) = &mut self.file {
+            file.inc(n);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(file) = self.file.take() {
+            file.finish();
+        }
+    }
+}
+
+impl Outcome {
+    fn skipped() -> Self {
+        Self {
+            action: Action::Skipped,
+            transferred: 0,
+            size: None,
+        }
+    }
+}
 
 pub async fn download(
-    client: Client,
+    mut client: Client,
     pathes: Vec<PathBuf>,
     volume_name: &str,
     output_dir: PathBuf,
+    options: DownloadOptions,
 ) -> Result<()> {
     let volume_id_arc = Arc::new(VolumeID::from_str(volume_name)?);
-    let mut handles = vec![];
+    if let Some(bytes_per_sec) = options.limit_rate {
+        client.limit_rate(bytes_per_sec);
+    }
     let client_arc = Arc::new(client);
-    let output_dir_str = output_dir.to_str().unwrap().to_string();
-    let output_dir_str_arc = Arc::new(output_dir_str);
 
-    for mut path in pathes {
+    let (targets, mut results) = collect_targets(
+        &client_arc,
+        pathes,
+        &volume_id_arc,
+        &output_dir,
+        options.recursive,
+    )
+    .await?;
+    results.extend(transfer(client_arc, volume_id_arc, targets, options).await);
+    report(results)
+}
+
+/// makes the targets to download `pathes` into `output_dir`.
+/// if `recursive` is true, directories are walked and made in `output_dir`.
+/// the directories which could not be listed are returned as failed results to be reported.
+pub async fn collect_targets(
+    client: &Client,
+    pathes: Vec<PathBuf>,
+    volume_id: &VolumeID,
+    output_dir: &Path,
+    recursive: bool,
+) -> Result<(Vec<Target>, Vec<Transferred>)> {
+    let mut targets = Vec::with_capacity(pathes.len());
+    let mut failed = Vec::new();
+    let mut fail = |remote_path: PathBuf, local_path: PathBuf, error: Error| {
+        let target = Target {
+            remote_path,
+            local_path,
+            uploaded_at: None,
+            size: None,
+        };
+        failed.push(Ok((target, Err(error.into()))));
+    };
+    for path in pathes {
+        if recursive && client.stat(&path, volume_id).await?.is_dir {
+            let base = path.parent().unwrap_or(&path);
+            create_dir_all(output_dir.join(path.strip_prefix(base)?))?;
+            for entry in client.walk(&path, volume_id).await? {
+                match entry {
+                    Entry::Dir(segment) => {
+                        create_dir_all(output_dir.join(segment.path.strip_prefix(base)?))?
+                    }
+                    Entry::File(segment) => {
+                        let local_path = output_dir.join(segment.path.strip_prefix(base)?);
+                        targets.push(Target {
+                            remote_path: segment.path,
+                            local_path,
+                            uploaded_at: Some(segment.uploaded_at),
+                            size: segment.size,
+                        });
+                    }
+                    Entry::Error { path, error } => {
+                        let local_path = output_dir.join(path.strip_prefix(base)?);
+                        fail(path, local_path, error);
+                    }
+                }
+            }
+            continue;
+        }
+
+        match path.file_name() {
+            Some(filename) => {
+                let local_path = output_dir.join(filename);
+                targets.push(Target {
+                    remote_path: path,
+                    local_path,
+                    uploaded_at: None,
+                    size: None,
+                });
+            }
+            None => {
+                let error =
+                    Error::InvalidArgument(format!("{}: path must be filepath", path.display()));
+                fail(path, output_dir.to_path_buf(), error);
+            }
+        }
+    }
+
+    Ok((targets, failed))
+}
+
+/// downloads `targets` in parallel with a progress display
+pub async fn transfer(
+    client_arc: Arc<Client>,
+    volume_id_arc: Arc<VolumeID>,
+    targets: Vec<Target>,
+    options: DownloadOptions,
+) -> Vec<Transferred> {
+    let progress_arc = Arc::new(Progress::new(targets.len()));
+    let semaphore_arc = Arc::new(Semaphore::new(options.jobs.max(1)));
+    let mut handles = vec![];
+    for target in targets {
         let client = client_arc.clone();
-        let output_dir_str = output_dir_str_arc.clone();
         let volume_id = volume_id_arc.clone();
+        let progress = progress_arc.clone();
+        let semaphore = semaphore_arc.clone();
         let handle = tokio::spawn(async move {
-            let filename = match path.file_name() {
-                Some(osstr) => osstr.to_str().unwrap().to_owned(),
-                None => bail!("path must be filepath"),
+            let _permit = semaphore.acquire().await.unwrap();
+            let mut watch = ProgressWatch {
+                progress: &progress,
+                name: target.remote_path.to_string_lossy().to_string(),
+                file: None,
             };
-            path.pop();
-
-            let bytes = client.download(path, &filename, &volume_id).await?;
-            let mut f = match File::create(format!("{}/{}", output_dir_str, &filename)) {
-                Ok(f) => f,
-                Err(_e) => todo!("if file has already existed..."),
-            };
-            f.write_all(&bytes)?;
-            Ok(())
+            let result = download_file(&client, &target, &volume_id, options, &mut watch).await;
+            progress.finish_file();
+            (target, result)
         });
         handles.push(handle);
     }
 
     let results = join_all(handles).await;
-    for join_res in &results {
-        if let Err(e) = join_res {
-            eprintln!("failed to join an async handle{}", e);
-        } else if let Ok(Err(e)) = join_res {
-            eprintln!("{}", e);
+    progress_arc.finish();
+    results
+}
+
+/// prints the action taken for each file and the summary.
+/// the error of the last failed file is returned.
+pub fn report(results: Vec<Transferred>) -> Result<()> {
+    let (mut n_downloaded, mut n_skipped, mut n_failed, mut n_bytes) = (0, 0, 0, 0);
+    let mut last_error = None;
+    for join_res in results {
+        match join_res {
+            Err(e) => {
+                n_failed += 1;
+                eprintln!("failed to join an async handle{}", e);
+                last_error = Some(e.into());
+            }
+            Ok((target, Err(e))) => {
+                n_failed += 1;
+                eprintln!("{:<12}{}: {}", "failed", target.remote_path.display(), e);
+                last_error = Some(e);
+            }
+            Ok((target, Ok(outcome))) => {
+                n_bytes += outcome.transferred;
+                let size = match outcome.size.or(target.size) {
+                    Some(size) => format!(" ({})", size.format(SizeFormat::Binary).trim()),
+                    None => String::new(),
+                };
+                match &outcome.action {
+                    Action::Skipped => n_skipped += 1,
+                    _ => n_downloaded += 1,
+                }
+                match &outcome.action {
+                    Action::Renamed(local_path) => eprintln!(
+                        "{:<12}{} -> {}{}",
+                        outcome.action,
+                        target.remote_path.display(),
+                        local_path.display(),
+                        size
+                    ),
+                    action => eprintln!("{:<12}{}{}", action, target.remote_path.display(), size),
+                }
+            }
+        }
+    }
+    eprintln!(
+        "{} downloaded, {} skipped, {} failed ({} transferred)",
+        n_downloaded,
+        n_skipped,
+        n_failed,
+        HumanBytes(n_bytes)
+    );
+    match last_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// downloads `target` through a `.part` file, following `on_conflict` and `resume` of `options`
+pub async fn download_file(
+    client: &Client,
+    target: &Target,
+    volume_id: &VolumeID,
+    options: DownloadOptions,
+    watch: &mut impl Watch,
+) -> Result<Outcome> {
+    let filename = target
+        .remote_path
+        .file_name()
+        .ok_or_else(|| anyhow!("path must be filepath"))?
+        .to_str()
+        .unwrap();
+    let dir = target
+        .remote_path
+        .parent()
+        .unwrap_or_else(|| Path::new("/"));
+
+    let exists = target.local_path.exists();
+    if exists {
+        match options.on_conflict {
+            OnConflict::Skip => return Ok(Outcome::skipped()),
+            OnConflict::Newer => {
+                let remote_uploaded_at = match target.uploaded_at {
+                    Some(uploaded_at) => uploaded_at,
+                    None => {
+                        client
+                            .list(dir, volume_id)
+                            .await?
+                            .into_iter()
+                            .find(|segment| segment.name == filename)
+                            .ok_or_else(|| Error::NotFound(target.remote_path.clone()))?
+                            .uploaded_at
+                    }
+                };
+                let local_modified_at: DateTime<Local> =
+                    metadata(&target.local_path)?.modified()?.into();
+                if remote_uploaded_at <= local_modified_at.naive_local() {
+                    return Ok(Outcome::skipped());
+                }
+            }
+            _ => (),
         }
     }
 
-    Ok(())
+    let part_path = part_path(&target.local_path);
+    let (transferred, size) = match download_to_part(
+        client,
+        dir,
+        filename,
+        volume_id,
+        &part_path,
+        options.resume,
+        watch,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            // keep the partially written file to resume next time
+            if !options.resume {
+                let _ = remove_file(&part_path);
+            }
+            return Err(e);
+        }
+    };
+
+    let action = match (exists, options.on_conflict) {
+        (false, _) => Action::Created,
+        (true, OnConflict::Rename) => Action::Renamed(create_renamed(&target.local_path)?),
+        (true, _) => Action::Overwritten,
+    };
+    let local_path = match &action {
+        Action::Renamed(local_path) => local_path,
+        _ => &target.local_path,
+    };
+    rename(&part_path, local_path)?;
+    Ok(Outcome {
+        action,
+        transferred,
+        size,
+    })
+}
+
+/// `name.ext` -> `name.ext.part`
+fn part_path(path: &Path) -> PathBuf {
+    let mut filename = path.file_name().unwrap_or_default().to_os_string();
+    filename.push(".part");
+    path.with_file_name(filename)
+}
+
+async fn download_to_part(
+    client: &Client,
+    dir: &Path,
+    filename: &str,
+    volume_id: &VolumeID,
+    part_path: &Path,
+    resume: bool,
+    watch: &mut impl Watch,
+) -> Result<(u64, Option<Size>)> {
+    let offset = match metadata(part_path) {
+        Ok(m) if resume => m.len(),
+        _ => 0,
+    };
+    let mut stream = client
+        .download_stream_from(dir, filename, volume_id, offset)
+        .await?;
+    let mut f = if stream.offset() > 0 {
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(part_path)
+            .await?
+    } else {
+        tokio::fs::File::create(part_path).await?
+    };
+
+    let size = stream.file_size();
+    watch.start(size.map(|size| size.bytes()), stream.offset());
+    let mut written = 0;
+    while let Some(chunk) = stream.chunk().await? {
+        f.write_all(&chunk).await?;
+        watch.inc(chunk.len() as u64);
+        written += chunk.len() as u64;
+    }
+    f.flush().await?;
+    watch.finish();
+    Ok((written, size))
+}
+
+/// create the first non-existing file among `name(1).ext`, `name(2).ext`, ...
+fn create_renamed(path: &Path) -> Result<PathBuf> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    for i in 1.. {
+        let candidate = path.with_file_name(format!("{}({}){}", stem, i, ext));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(_) => return Ok(candidate),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{download, DownloadOptions, OnConflict};
+    use cvpn::api::mock::{listing, serve, Response};
+    use cvpn::{api::Client, Error};
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, read, remove_dir_all},
+        path::PathBuf,
+    };
+
+    #[tokio::test]
+    pub async fn download_missing() {
+        let base_url =
+            serve(|_| Response::html("The file or folder does not exist on the server.")).await;
+        let client = Client::with_base_url(base_url).unwrap();
+        let options = DownloadOptions {
+            recursive: false,
+            on_conflict: OnConflict::Overwrite,
+            resume: false,
+            jobs: 1,
+            limit_rate: None,
+        };
+        let err = download(
+            client,
+            vec![PathBuf::from("/missing.txt")],
+            "fsshare",
+            temp_dir(),
+            options,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::NotFound(p)) if p.to_str() == Some("/missing.txt")
+        ));
+    }
+
+    #[tokio::test]
+    pub async fn download_recursive() {
+        let base_url = serve(|req| {
+            if req.target.starts_with("/dana/download/a.txt") {
+                return Response {
+                    status: "200 OK",
+                    headers: vec![("Content-Type", "application/octet-stream".to_string())],
+                    body: b"a".to_vec(),
+                };
+            }
+            if req.target.ends_with("dir=%2F") {
+                listing(&["r"], &[])
+            } else if req.target.ends_with("dir=%2Fr") {
+                listing(&["locked"], &["a.txt"])
+            } else {
+                // the web UI is redirected to the home page if the directory cannot be read
+                Response::redirect("/dana/home/index.cgi")
+            }
+        })
+        .await;
+        let dir = temp_dir().join(format!("cvpn-download-test-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let options = DownloadOptions {
+            recursive: true,
+            on_conflict: OnConflict::Overwrite,
+            resume: false,
+            jobs: 1,
+            limit_rate: None,
+        };
+
+        // the directory which cannot be listed is a failure
+        let client = Client::with_base_url(base_url.clone()).unwrap();
+        let err = download(
+            client,
+            vec![PathBuf::from("/r")],
+            "fsshare",
+            dir.clone(),
+            options,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::PermissionDenied(p)) if p.to_str() == Some("/r/locked")
+        ));
+        assert_eq!(read(dir.join("r/a.txt")).unwrap(), b"a");
+
+        // it is not downloaded as a file
+        let client = Client::with_base_url(base_url).unwrap();
+        let err = download(
+            client,
+            vec![PathBuf::from("/r/locked/b.txt")],
+            "fsshare",
+            dir.clone(),
+            options,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::PermissionDenied(_))
+        ));
+        assert!(!dir.join("b.txt").exists());
+
+        remove_dir_all(&dir).unwrap();
+    }
 }
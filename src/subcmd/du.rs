@@ -0,0 +1,86 @@
+use anyhow::Result;
+use cvpn::api::{
+    model::{
+        size::{Size, SizeFormat},
+        volume_id::VolumeID,
+    },
+    walk::{Children, Node},
+    Client,
+};
+use std::{
+    io::{stdout, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub async fn du(
+    client: Client,
+    path: PathBuf,
+    volume_name: &str,
+    max_depth: Option<usize>,
+    human_readable: bool,
+    jobs: usize,
+) -> Result<()> {
+    let volume_id = VolumeID::from_str(volume_name)?;
+    let nodes = client.walk_tree(&path, &volume_id, None, jobs).await?;
+
+    let size_format = if human_readable {
+        SizeFormat::Binary
+    } else {
+        SizeFormat::Bytes
+    };
+    let mut output = String::new();
+    let total = write_usage(&mut output, &nodes, &path, 0, max_depth, size_format);
+    print!("{}", output);
+    stdout().flush()?;
+
+    if !total.is_exact() {
+        eprintln!(
+            "note: sizes marked with '~' are approximate because the server only shows rounded sizes (e.g. 12.3 MB)"
+        );
+    }
+    Ok(())
+}
+
+/// writes the usage of the directory `path` and its subdirectories up to `max_depth`
+/// in post-order, and returns the total size of `path`
+fn write_usage(
+    output: &mut String,
+    nodes: &[Node],
+    path: &Path,
+    depth: usize,
+    max_depth: Option<usize>,
+    size_format: SizeFormat,
+) -> Size {
+    let mut total = Size::zero();
+    for node in nodes {
+        match node {
+            Node::File(segment) => total = total + segment.size.unwrap_or_else(Size::zero),
+            Node::Dir { segment, children } => match children {
+                Children::Listed(children) => {
+                    total = total
+                        + write_usage(
+                            output,
+                            children,
+                            &segment.path,
+                            depth + 1,
+                            max_depth,
+                            size_format,
+                        )
+                }
+                Children::Unlisted => (),
+                Children::Error(e) => eprintln!("{}: {}", segment.path.display(), e),
+            },
+        }
+    }
+
+    if max_depth.is_none_or(|max_depth| depth <= max_depth) {
+        output.push_str(&format!(
+            "{}{:<12}{}\n",
+            if total.is_exact() { " " } else { "~" },
+            total.format(size_format).trim(),
+            path.display()
+        ));
+    }
+    total
+}
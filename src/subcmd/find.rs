@@ -0,0 +1,267 @@
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::Args;
+use cvpn::api::{
+    model::{segment::Segment, size::Size, volume_id::VolumeID},
+    walk::Entry,
+    Client,
+};
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use std::{
+    io::{stdout, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// options which `find` accepts with a single dash (e.g. `-name`)
+const SINGLE_DASH_OPTIONS: &[&str] = &[
+    "-name", "-iname", "-regex", "-size", "-newer", "-older", "-type", "-print0",
+];
+
+#[derive(Debug, Args)]
+pub struct Predicates {
+    /// glob pattern matched against the file name (e.g. '*.pdf')
+    #[clap(long)]
+    pub name: Option<Pattern>,
+    /// same as -name, but case insensitive
+    #[clap(long)]
+    pub iname: Option<Pattern>,
+    /// regular expression searched in the file name
+    #[clap(long)]
+    pub regex: Option<Regex>,
+    /// size in bytes or with a unit (K, M, G). `+1M` is larger than and `-1M` is smaller than 1M.
+    #[clap(long, allow_hyphen_values = true)]
+    pub size: Option<SizeFilter>,
+    /// uploaded after the date (e.g. 2022-10-01 or 2022-10-01T12:00:00)
+    #[clap(long, parse(try_from_str = parse_datetime))]
+    pub newer: Option<NaiveDateTime>,
+    /// uploaded before the date
+    #[clap(long, parse(try_from_str = parse_datetime))]
+    pub older: Option<NaiveDateTime>,
+    /// f (file) or d (directory)
+    #[clap(long = "type")]
+    pub kind: Option<Kind>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Kind {
+    File,
+    Dir,
+}
+
+impl FromStr for Kind {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "f" => Ok(Self::File),
+            "d" => Ok(Self::Dir),
+            _ => Err("{f, d}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SizeFilter {
+    Larger(Size),
+    Smaller(Size),
+    Equal(Size),
+}
+
+impl FromStr for SizeFilter {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(s) = s.strip_prefix('+') {
+            Ok(Self::Larger(s.parse()?))
+        } else if let Some(s) = s.strip_prefix('-') {
+            Ok(Self::Smaller(s.parse()?))
+        } else {
+            Ok(Self::Equal(s.parse()?))
+        }
+    }
+}
+
+fn parse_datetime(s: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|date| date.and_hms(0, 0, 0)))
+        .map_err(|_| format!("invalid date: {}", s))
+}
+
+impl Predicates {
+    pub fn matches(&self, segment: &Segment) -> bool {
+        let case_insensitive = MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        if let Some(pattern) = &self.name {
+            if !pattern.matches(&segment.name) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.iname {
+            if !pattern.matches_with(&segment.name, case_insensitive) {
+                return false;
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&segment.name) {
+                return false;
+            }
+        }
+        if let Some(filter) = &self.size {
+            // directories have no size
+            let size = match segment.size {
+                Some(size) => size,
+                None => return false,
+            };
+            let matched = match filter {
+                SizeFilter::Larger(s) => size > *s,
+                SizeFilter::Smaller(s) => size < *s,
+                // sizes in listings are rounded, so compare in the unit of the filter
+                SizeFilter::Equal(s) => {
+                    let unit = s.unit().bytes();
+                    size.bytes().div_ceil(unit) == s.bytes() / unit
+                }
+            };
+            if !matched {
+                return false;
+            }
+        }
+        if let Some(newer) = &self.newer {
+            if segment.uploaded_at <= *newer {
+                return false;
+            }
+        }
+        if let Some(older) = &self.older {
+            if segment.uploaded_at >= *older {
+                return false;
+            }
+        }
+        match self.kind {
+            Some(Kind::File) => segment.is_file,
+            Some(Kind::Dir) => segment.is_dir,
+            None => true,
+        }
+    }
+}
+
+/// rewrites the single-dash options of `find` (e.g. `-name`) to the double-dash ones.
+/// `value_options` are the options before the subcommand which take a value (e.g. `--realm`),
+/// whose values are not taken for the subcommand.
+pub fn normalize_args<I: IntoIterator<Item = String>>(
+    args: I,
+    value_options: &[String],
+) -> Vec<String> {
+    let mut args = args.into_iter().collect::<Vec<_>>();
+    let mut subcmd = None;
+    let mut rest = args.iter().enumerate().skip(1);
+    while let Some((i, arg)) = rest.next() {
+        if value_options.contains(arg) {
+            rest.next();
+        } else if !arg.starts_with('-') {
+            subcmd = Some(i);
+            break;
+        }
+    }
+    let i = match subcmd {
+        Some(i) if args[i] == "find" => i,
+        _ => return args,
+    };
+    for arg in args[i + 1..].iter_mut() {
+        if SINGLE_DASH_OPTIONS.contains(&arg.as_str()) {
+            arg.insert(0, '-');
+        }
+    }
+    args
+}
+
+pub async fn find(
+    client: Client,
+    path: PathBuf,
+    volume_name: &str,
+    predicates: Predicates,
+    print0: bool,
+) -> Result<()> {
+    let volume_id = VolumeID::from_str(volume_name)?;
+    let entries = client.walk(path, &volume_id).await?;
+
+    let mut output = String::new();
+    for entry in entries {
+        let segment = match entry {
+            Entry::File(segment) | Entry::Dir(segment) => segment,
+            Entry::Error { path, error } => {
+                eprintln!("{}: {}", path.display(), error);
+                continue;
+            }
+        };
+        if predicates.matches(&segment) {
+            output.push_str(segment.path.to_str().unwrap());
+            output.push(if print0 { '\0' } else { '\n' });
+        }
+    }
+    print!("{}", output);
+    stdout().flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalize_args, SizeFilter};
+    use cvpn::api::model::size::{Size, Unit};
+
+    #[test]
+    pub fn normalize_find_args() {
+        let value_options = ["--realm", "--tz-offset"].map(String::from);
+        let args = ["cvpn", "find", "/class", "-name", "*.pdf", "-size", "-1M"].map(String::from);
+        assert_eq!(
+            normalize_args(args, &value_options),
+            ["cvpn", "find", "/class", "--name", "*.pdf", "--size", "-1M"]
+        );
+        let args = ["cvpn", "list", "-name"].map(String::from);
+        assert_eq!(
+            normalize_args(args, &value_options),
+            ["cvpn", "list", "-name"]
+        );
+
+        // the options before the subcommand
+        let args = [
+            "cvpn",
+            "--realm",
+            "Staff-Realm",
+            "--tz-offset=540",
+            "find",
+            "/class",
+            "-name",
+            "*.pdf",
+        ]
+        .map(String::from);
+        assert_eq!(
+            normalize_args(args, &value_options),
+            [
+                "cvpn",
+                "--realm",
+                "Staff-Realm",
+                "--tz-offset=540",
+                "find",
+                "/class",
+                "--name",
+                "*.pdf"
+            ]
+        );
+        let args = ["cvpn", "--realm", "find", "list", "-name"].map(String::from);
+        assert_eq!(
+            normalize_args(args, &value_options),
+            ["cvpn", "--realm", "find", "list", "-name"]
+        );
+    }
+
+    #[test]
+    pub fn parse_size_filter() {
+        let one_mb = Size::new(1.0, Unit::MB);
+        assert!(matches!("+1M".parse(), Ok(SizeFilter::Larger(s)) if s == one_mb));
+        assert!(matches!("-1M".parse(), Ok(SizeFilter::Smaller(s)) if s == one_mb));
+        assert!(matches!("1M".parse(), Ok(SizeFilter::Equal(s)) if s == one_mb));
+        assert!("1X".parse::<SizeFilter>().is_err());
+    }
+}
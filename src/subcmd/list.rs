@@ -1,15 +1,15 @@
-use crate::api::{
-    model::{size::Size, volume_id::VolumeID},
+use anyhow::Result;
+use cvpn::api::{
+    model::{segment::Segment, size::SizeFormat, volume_id::VolumeID},
     Client,
 };
-use anyhow::Result;
 use std::{
     io::{stdout, Write},
     path::PathBuf,
     str::FromStr,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Sort {
     Size,
     Date,
@@ -20,7 +20,7 @@ pub enum Sort {
 impl FromStr for Sort {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match &*s {
+        match s {
             "size" => Ok(Self::Size),
             "date" => Ok(Self::Date),
             "name" => Ok(Self::Name),
@@ -30,38 +30,125 @@ impl FromStr for Sort {
     }
 }
 
+#[derive(Debug)]
+pub enum Format {
+    Table,
+    Json,
+    /// one JSON object per line
+    Ndjson,
+    Csv,
+    Tsv,
+}
+
+impl FromStr for Format {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            _ => Err("{table, json, ndjson, csv, tsv}"),
+        }
+    }
+}
+
 pub async fn list(
     client: Client,
     path: PathBuf,
     volume_name: &str,
     sort_by: Sort,
     name_only: bool,
+    format: Format,
+    size_format: SizeFormat,
 ) -> Result<()> {
     let volume_id = VolumeID::from_str(volume_name)?;
     let mut segments = client.list(path, &volume_id).await?;
 
+    sort(&mut segments, sort_by);
+
+    let output = match format {
+        _ if name_only => {
+            let mut output = String::new();
+            for segment in segments {
+                output.push_str(&(segment.path.to_str().unwrap().to_string() + "\n"))
+            }
+            output
+        }
+        Format::Table => {
+            let mut output = String::new();
+            for segment in segments {
+                output.push_str(&(segment.to_string_with(size_format) + "\n"));
+            }
+            output
+        }
+        Format::Json | Format::Ndjson => to_json(&segments, matches!(format, Format::Ndjson))?,
+        Format::Csv => to_separated(&segments, ','),
+        Format::Tsv => to_separated(&segments, '\t'),
+    };
+    print!("{}", output);
+    stdout().flush()?;
+    Ok(())
+}
+
+/// sorts `segments` by `sort_by`. `Sort::None` keeps the order of the server.
+pub fn sort(segments: &mut [Segment], sort_by: Sort) {
     match sort_by {
         Sort::Date => segments.sort_by_key(|segment| segment.uploaded_at),
         Sort::Name => segments.sort_by(|l, r| l.name.cmp(&r.name)),
-        Sort::Size => segments.sort_by(|l, r| {
-            l.size
-                .as_ref()
-                .unwrap_or(&Size::zero())
-                .partial_cmp(r.size.as_ref().unwrap_or(&Size::zero()))
-                .unwrap()
-        }),
+        Sort::Size => segments.sort_by_key(|segment| segment.size),
         _ => (),
     }
+}
 
+#[cfg(feature = "serde")]
+fn to_json(segments: &[Segment], ndjson: bool) -> Result<String> {
+    if !ndjson {
+        return Ok(serde_json::to_string_pretty(segments)? + "\n");
+    }
     let mut output = String::new();
     for segment in segments {
-        if name_only {
-            output.push_str(&(segment.path.to_str().unwrap().to_string() + "\n"))
+        output.push_str(&(serde_json::to_string(segment)? + "\n"));
+    }
+    Ok(output)
+}
+
+#[cfg(not(feature = "serde"))]
+fn to_json(_segments: &[Segment], _ndjson: bool) -> Result<String> {
+    anyhow::bail!("JSON output requires the `serde` feature")
+}
+
+/// CSV (RFC 4180) if `sep` is ',', otherwise fields are written as is without tabs and newlines.
+fn to_separated(segments: &[Segment], sep: char) -> String {
+    let field = |s: &str| -> String {
+        if sep == ',' {
+            if s.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            } else {
+                s.to_string()
+            }
         } else {
-            output.push_str(&(segment.to_string() + "\n"));
+            s.replace(['\t', '\n', '\r'], " ")
         }
+    };
+
+    let mut output =
+        ["name", "path", "volume", "size", "is_dir", "uploaded_at"].join(&sep.to_string()) + "\n";
+    for segment in segments {
+        let row = [
+            field(&segment.name),
+            field(&segment.path.to_string_lossy()),
+            field(&segment.volume_id),
+            segment
+                .size
+                .as_ref()
+                .map(|size| size.bytes().to_string())
+                .unwrap_or_default(),
+            segment.is_dir.to_string(),
+            segment.uploaded_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        ];
+        output.push_str(&(row.join(&sep.to_string()) + "\n"));
     }
-    print!("{}", output);
-    stdout().flush()?;
-    Ok(())
+    output
 }
@@ -0,0 +1,10 @@
+use crate::prompt::input_account;
+use anyhow::Result;
+use cvpn::api::config::ClientConfig;
+
+/// asks the account again and overwrites the saved one
+pub async fn login(config: ClientConfig) -> Result<()> {
+    let (username, _) = input_account(&config).await?;
+    eprintln!("logged in as {}", username);
+    Ok(())
+}
@@ -0,0 +1,16 @@
+use anyhow::Result;
+use cvpn::{
+    api::{config::ClientConfig, Client},
+    appdata::cookies_saved_at,
+};
+
+/// ends the saved session. the account is kept.
+pub async fn logout(config: ClientConfig) -> Result<()> {
+    if cookies_saved_at().is_err() {
+        eprintln!("not logged in");
+        return Ok(());
+    }
+    Client::with_token(config)?.logout().await?;
+    eprintln!("logged out");
+    Ok(())
+}
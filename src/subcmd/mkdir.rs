@@ -0,0 +1,138 @@
+use anyhow::Result;
+use cvpn::{
+    api::{model::volume_id::VolumeID, Client},
+    Error,
+};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// makes each directory. errors are printed to stderr, and the last one is returned
+/// after all directories are tried.
+pub async fn mkdir(
+    client: Client,
+    pathes: Vec<PathBuf>,
+    volume_name: &str,
+    parents: bool,
+) -> Result<()> {
+    let volume_id = VolumeID::from_str(volume_name)?;
+    let mut last_error = None;
+    for path in pathes {
+        let result = if parents {
+            mkdir_all(&client, &path, &volume_id).await
+        } else {
+            mkdir_one(&client, &path, &volume_id).await
+        };
+        if let Err(e) = result {
+            if let Some(prev) = last_error.replace(e) {
+                eprintln!("{}", prev);
+            }
+        }
+    }
+    match last_error {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+async fn mkdir_one(client: &Client, path: &Path, volume_id: &VolumeID) -> cvpn::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| {
+            Error::InvalidArgument(format!("cannot create directory '{}'", path.display()))
+        })?
+        .to_str()
+        .unwrap();
+    client
+        .mkdir(
+            path.parent().unwrap_or_else(|| Path::new("/")),
+            name,
+            volume_id,
+        )
+        .await
+}
+
+/// makes `path` and its missing ancestors. existing directories are not errors.
+/// only the missing ones are made, since the user may not be able to write to the existing ones.
+async fn mkdir_all(client: &Client, path: &Path, volume_id: &VolumeID) -> cvpn::Result<()> {
+    let mut missing = Vec::new();
+    for dir in path.ancestors().filter(|dir| dir.file_name().is_some()) {
+        match client.stat(dir, volume_id).await {
+            Ok(segment) if segment.is_dir => break,
+            Ok(_) => return Err(Error::AlreadyExists(dir.to_path_buf())),
+            Err(Error::NotFound(_)) => missing.push(dir),
+            Err(e) => return Err(e),
+        }
+    }
+    for dir in missing.into_iter().rev() {
+        match mkdir_one(client, dir, volume_id).await {
+            Ok(()) | Err(Error::AlreadyExists(_)) => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::mkdir;
+    use cvpn::api::mock::{error_page, listing, serve, Response, HOME_PAGE};
+    use cvpn::api::Client;
+    use std::{
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    /// the user cannot write to `/home`
+    #[tokio::test]
+    pub async fn mkdir_parents() {
+        let created = Arc::new(Mutex::new(Vec::new()));
+        let created_by_server = created.clone();
+        let base_url = serve(move |req| {
+            if req.target == "/dana/home/index.cgi" {
+                return Response::html(HOME_PAGE);
+            }
+            if req.method == "POST" {
+                let body = req.body_text();
+                let field = |name: &str| {
+                    body.split('&')
+                        .find_map(|pair| pair.strip_prefix(&format!("{}=", name)))
+                        .unwrap()
+                        .replace("%2F", "/")
+                };
+                if field("dir") == "/" {
+                    return error_page("You do not have permission to write.");
+                }
+                created_by_server
+                    .lock()
+                    .unwrap()
+                    .push(Path::new(&field("dir")).join(field("newname")));
+                return Response::redirect("/dana/fb/smb/wfb.cgi");
+            }
+            if req.target.ends_with("dir=%2F") {
+                listing(&["home"], &[])
+            } else if req.target.ends_with("dir=%2Fhome") {
+                listing(&["user"], &[])
+            } else if req.target.ends_with("dir=%2Fhome%2Fuser") {
+                listing(&[], &[])
+            } else {
+                Response::html("")
+            }
+        })
+        .await;
+        let client = Client::with_base_url(base_url).unwrap();
+        mkdir(
+            client,
+            vec![PathBuf::from("/home/user/a/b")],
+            "fsshare",
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            *created.lock().unwrap(),
+            ["/home/user/a", "/home/user/a/b"].map(PathBuf::from)
+        );
+    }
+}
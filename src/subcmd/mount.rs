@@ -0,0 +1,511 @@
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use cvpn::{
+    api::{
+        download::DownloadStream,
+        model::{segment::Segment, volume_id::VolumeID},
+        Client,
+    },
+    Error,
+};
+use fuser::{
+    consts::FOPEN_DIRECT_IO, FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData,
+    ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, Request,
+};
+use libc::{EACCES, EIO, EISDIR, ENOENT};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::runtime::Handle;
+
+const ROOT_INO: u64 = 1;
+/// files are downloaded and cached in blocks of this size
+const BLOCK_SIZE: u64 = 1 << 20;
+/// the maximum number of cached blocks
+const MAX_BLOCKS: usize = 64;
+
+struct Listing {
+    fetched_at: Instant,
+    segments: Vec<Segment>,
+}
+
+struct Block {
+    fetched_at: Instant,
+    bytes: Bytes,
+}
+
+/// the download left open by the last read of a file handle.
+/// sequential reads continue it, so that a server which ignores `Range` does not
+/// send the file from the beginning for every block.
+struct OpenStream {
+    ino: u64,
+    stream: DownloadStream,
+    /// the offset in the file of the first byte of `pending`
+    position: u64,
+    /// the bytes received but not read yet
+    pending: Bytes,
+    /// the server sent the file from the beginning though a later offset was requested
+    ignores_range: bool,
+}
+
+/// a read-only filesystem of the remote directory.
+/// listings and blocks of files are cached for `ttl`.
+struct RemoteFs {
+    client: Client,
+    volume_id: VolumeID,
+    handle: Handle,
+    ttl: Duration,
+    /// the path of each inode. the inode of `pathes[i]` is `i + 1`.
+    pathes: Vec<PathBuf>,
+    inodes: HashMap<PathBuf, u64>,
+    listings: HashMap<u64, Listing>,
+    /// the sizes known from downloads, with the upload date of the file they belong to.
+    /// sizes in listings are rounded by the server.
+    exact_sizes: HashMap<u64, (NaiveDateTime, u64)>,
+    /// keyed by the inode and the index of the block
+    blocks: HashMap<(u64, u64), Block>,
+    /// keyed by the file handle
+    streams: HashMap<u64, OpenStream>,
+    next_fh: u64,
+    uid: u32,
+    gid: u32,
+}
+
+pub async fn mount(
+    client: Client,
+    path: PathBuf,
+    mountpoint: PathBuf,
+    volume_name: &str,
+    ttl: Duration,
+) -> Result<()> {
+    let fs = RemoteFs {
+        client,
+        volume_id: VolumeID::from_str(volume_name)?,
+        handle: Handle::current(),
+        ttl,
+        pathes: vec![path.clone()],
+        inodes: HashMap::from([(path.clone(), ROOT_INO)]),
+        listings: HashMap::new(),
+        exact_sizes: HashMap::new(),
+        blocks: HashMap::new(),
+        streams: HashMap::new(),
+        next_fh: 0,
+        // SAFETY: these functions always succeed
+        uid: unsafe { libc::getuid() },
+        gid: unsafe { libc::getgid() },
+    };
+    let options = [
+        MountOption::RO,
+        MountOption::FSName("cvpn".to_string()),
+        MountOption::Subtype(volume_name.to_string()),
+    ];
+    let session = fuser::spawn_mount2(fs, &mountpoint, &options)?;
+    eprintln!(
+        "mounted {} on {}. press Ctrl-C to unmount.",
+        path.display(),
+        mountpoint.display()
+    );
+
+    // until Ctrl-C or `fusermount -u`
+    loop {
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                break;
+            }
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {
+                if session.guard.is_finished() {
+                    break;
+                }
+            }
+        }
+    }
+    drop(session);
+    Ok(())
+}
+
+impl RemoteFs {
+    fn ino(&mut self, path: &Path) -> u64 {
+        if let Some(ino) = self.inodes.get(path) {
+            return *ino;
+        }
+        self.pathes.push(path.to_path_buf());
+        let ino = self.pathes.len() as u64;
+        self.inodes.insert(path.to_path_buf(), ino);
+        ino
+    }
+
+    fn path(&self, ino: u64) -> Option<&Path> {
+        self.pathes.get(ino as usize - 1).map(|path| path.as_path())
+    }
+
+    /// lists the directory of `ino` unless the cached listing is fresh
+    fn listing(&mut self, ino: u64) -> Result<&[Segment]> {
+        let is_fresh = self
+            .listings
+            .get(&ino)
+            .is_some_and(|listing| listing.fetched_at.elapsed() < self.ttl);
+        if !is_fresh {
+            let path = self.path(ino).ok_or_else(|| anyhow!("unknown inode"))?;
+            let segments = self
+                .handle
+                .block_on(self.client.list(path, &self.volume_id))?;
+            self.listings.insert(
+                ino,
+                Listing {
+                    fetched_at: Instant::now(),
+                    segments,
+                },
+            );
+        }
+        Ok(&self.listings[&ino].segments)
+    }
+
+    /// the segment of `ino` from the listing of its parent. the root has no segment.
+    fn segment(&mut self, ino: u64) -> Result<Option<Segment>> {
+        if ino == ROOT_INO {
+            return Ok(None);
+        }
+        let path = self
+            .path(ino)
+            .ok_or_else(|| anyhow!("unknown inode"))?
+            .to_path_buf();
+        let parent = self.ino(path.parent().unwrap_or_else(|| Path::new("/")));
+        let name = path.file_name().unwrap_or_default();
+        let segment = self
+            .listing(parent)?
+            .iter()
+            .find(|segment| OsStr::new(&segment.name) == name)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(path.clone()))?;
+        Ok(Some(segment))
+    }
+
+    fn attr(&self, ino: u64, segment: Option<&Segment>) -> FileAttr {
+        let mtime = segment.map_or(SystemTime::UNIX_EPOCH, |segment| {
+            to_system_time(segment.uploaded_at)
+        });
+        let (kind, perm, size) = match segment {
+            Some(segment) if segment.is_file => {
+                let size = match self.exact_sizes.get(&ino) {
+                    Some((uploaded_at, size)) if *uploaded_at == segment.uploaded_at => *size,
+                    _ => segment.size.map_or(0, |size| size.bytes()),
+                };
+                (FileType::RegularFile, 0o444, size)
+            }
+            _ => (FileType::Directory, 0o555, 0),
+        };
+        FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind,
+            perm,
+            nlink: 1,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: BLOCK_SIZE as u32,
+            flags: 0,
+        }
+    }
+
+    /// reads the `index`-th block of the file through the file handle `fh`.
+    /// a block shorter than `BLOCK_SIZE` is the last one.
+    fn block(&mut self, ino: u64, fh: u64, segment: &Segment, index: u64) -> Result<Bytes> {
+        if let Some(block) = self.blocks.get(&(ino, index)) {
+            if block.fetched_at.elapsed() < self.ttl {
+                return Ok(block.bytes.clone());
+            }
+        }
+
+        let offset = index * BLOCK_SIZE;
+        let dir = segment.path.parent().unwrap_or_else(|| Path::new("/"));
+        // skipping forward is cheaper than a new request only if the server ignores the range
+        let open = self.streams.remove(&fh).filter(|open| {
+            open.ino == ino
+                && (open.position == offset || open.ignores_range && open.position < offset)
+        });
+        let (bytes, open) = self.handle.block_on(async {
+            let mut open = match open {
+                Some(open) => open,
+                None => {
+                    let stream = self
+                        .client
+                        .download_stream_from(dir, &segment.name, &self.volume_id, offset)
+                        .await?;
+                    OpenStream {
+                        ino,
+                        position: stream.offset(),
+                        pending: Bytes::new(),
+                        ignores_range: stream.offset() < offset,
+                        stream,
+                    }
+                }
+            };
+            let mut bytes = Vec::with_capacity(BLOCK_SIZE as usize);
+            while bytes.len() < BLOCK_SIZE as usize {
+                if open.pending.is_empty() {
+                    match open.stream.chunk().await? {
+                        Some(chunk) => open.pending = chunk,
+                        None => break,
+                    }
+                }
+                let n_skipped =
+                    (offset.saturating_sub(open.position) as usize).min(open.pending.len());
+                let n = (open.pending.len() - n_skipped).min(BLOCK_SIZE as usize - bytes.len());
+                bytes.extend_from_slice(&open.pending[n_skipped..n_skipped + n]);
+                open.pending = open.pending.slice(n_skipped + n..);
+                open.position += (n_skipped + n) as u64;
+            }
+            anyhow::Ok((Bytes::from(bytes), open))
+        })?;
+        if bytes.len() as u64 == BLOCK_SIZE {
+            self.streams.insert(fh, open);
+        }
+
+        if (bytes.len() as u64) < BLOCK_SIZE {
+            self.exact_sizes
+                .insert(ino, (segment.uploaded_at, offset + bytes.len() as u64));
+        }
+        if self.blocks.len() >= MAX_BLOCKS {
+            let oldest = self
+                .blocks
+                .iter()
+                .min_by_key(|(_, block)| block.fetched_at)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.blocks.remove(&oldest);
+            }
+        }
+        self.blocks.insert(
+            (ino, index),
+            Block {
+                fetched_at: Instant::now(),
+                bytes: bytes.clone(),
+            },
+        );
+        Ok(bytes)
+    }
+}
+
+impl Filesystem for RemoteFs {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let segment = match self.listing(parent) {
+            Ok(segments) => segments
+                .iter()
+                .find(|segment| OsStr::new(&segment.name) == name)
+                .cloned(),
+            Err(e) => return reply.error(errno(&e)),
+        };
+        match segment {
+            Some(segment) => {
+                let ino = self.ino(&segment.path);
+                reply.entry(&self.ttl, &self.attr(ino, Some(&segment)), 0)
+            }
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.segment(ino) {
+            Ok(segment) => reply.attr(&self.ttl, &self.attr(ino, segment.as_ref())),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let segments = match self.listing(ino) {
+            Ok(segments) => segments.to_vec(),
+            Err(e) => return reply.error(errno(&e)),
+        };
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_string()),
+            (ino, FileType::Directory, "..".to_string()),
+        ];
+        for segment in segments {
+            let kind = if segment.is_dir {
+                FileType::Directory
+            } else {
+                FileType::RegularFile
+            };
+            entries.push((self.ino(&segment.path), kind, segment.name));
+        }
+        for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            // the offset of an entry is the offset of the next one
+            if reply.add(ino, i as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+        match self.segment(ino) {
+            // the size in the listing is approximate, so reads must not be cut at it
+            Ok(Some(segment)) if segment.is_file => {
+                self.next_fh += 1;
+                reply.opened(self.next_fh, FOPEN_DIRECT_IO)
+            }
+            Ok(_) => reply.error(EISDIR),
+            Err(e) => reply.error(errno(&e)),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let segment = match self.segment(ino) {
+            Ok(Some(segment)) => segment,
+            Ok(None) => return reply.error(EISDIR),
+            Err(e) => return reply.error(errno(&e)),
+        };
+        let (start, end) = (offset as u64, offset as u64 + size as u64);
+        let mut data = Vec::with_capacity(size as usize);
+        for index in start / BLOCK_SIZE..end.div_ceil(BLOCK_SIZE) {
+            let block = match self.block(ino, fh, &segment, index) {
+                Ok(block) => block,
+                Err(e) => return reply.error(errno(&e)),
+            };
+            let block_start = index * BLOCK_SIZE;
+            let from = start.saturating_sub(block_start).min(block.len() as u64) as usize;
+            let to = (end - block_start).min(block.len() as u64) as usize;
+            data.extend_from_slice(&block[from..to]);
+            if (block.len() as u64) < BLOCK_SIZE {
+                break;
+            }
+        }
+        reply.data(&data);
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.streams.remove(&fh);
+        reply.ok();
+    }
+}
+
+fn to_system_time(datetime: NaiveDateTime) -> SystemTime {
+    match Local.from_local_datetime(&datetime).earliest() {
+        Some(datetime) => datetime.into(),
+        None => SystemTime::UNIX_EPOCH,
+    }
+}
+
+fn errno(e: &anyhow::Error) -> i32 {
+    match e.downcast_ref() {
+        Some(Error::NotFound(_)) => ENOENT,
+        Some(Error::PermissionDenied(_)) => EACCES,
+        _ => EIO,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RemoteFs, BLOCK_SIZE};
+    use chrono::NaiveDate;
+    use cvpn::api::mock::{serve, Response};
+    use cvpn::api::{
+        model::{segment::Segment, size::Size, volume_id::VolumeID},
+        Client,
+    };
+    use std::{
+        collections::HashMap,
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use tokio::runtime::Runtime;
+
+    /// the server ignores `Range` and always sends the whole file
+    #[test]
+    pub fn sequential_reads() {
+        let len = BLOCK_SIZE as usize * 5 / 2;
+        let content = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+        let n_requests = Arc::new(AtomicUsize::new(0));
+        let n_requests_by_server = n_requests.clone();
+        let body = content.clone();
+        let rt = Runtime::new().unwrap();
+        let base_url = rt.block_on(serve(move |_| {
+            n_requests_by_server.fetch_add(1, Ordering::Relaxed);
+            Response {
+                status: "200 OK",
+                headers: vec![("Content-Type", "application/octet-stream".to_string())],
+                body: body.clone(),
+            }
+        }));
+        let mut fs = RemoteFs {
+            client: Client::with_base_url(base_url).unwrap(),
+            volume_id: VolumeID::FSShare,
+            handle: rt.handle().clone(),
+            ttl: Duration::from_secs(60),
+            pathes: vec![PathBuf::from("/")],
+            inodes: HashMap::new(),
+            listings: HashMap::new(),
+            exact_sizes: HashMap::new(),
+            blocks: HashMap::new(),
+            streams: HashMap::new(),
+            next_fh: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let segment = Segment::from_file(
+            "a.bin".to_string(),
+            PathBuf::from("/a.bin"),
+            Size::from_bytes(len as u64),
+            VolumeID::FSShare.to_string(),
+            NaiveDate::from_ymd(2022, 4, 1).and_hms(10, 0, 0),
+        );
+
+        let mut read = Vec::new();
+        for index in 0..3 {
+            read.extend_from_slice(&fs.block(2, 1, &segment, index).unwrap());
+        }
+        assert_eq!(read, content);
+        assert_eq!(n_requests.load(Ordering::Relaxed), 1);
+
+        // another handle starts its own download
+        fs.blocks.clear();
+        let block = fs.block(2, 2, &segment, 1).unwrap();
+        assert_eq!(
+            block[..],
+            content[BLOCK_SIZE as usize..2 * BLOCK_SIZE as usize]
+        );
+        assert_eq!(n_requests.load(Ordering::Relaxed), 2);
+    }
+}
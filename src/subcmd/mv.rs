@@ -0,0 +1,149 @@
+use crate::prompt::confirm;
+use anyhow::{anyhow, bail, Result};
+use cvpn::{
+    api::{model::volume_id::VolumeID, Client},
+    Error,
+};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// renames `src` to `dst` in the same directory.
+/// `dst` may be a name or a path in the directory of `src`.
+pub async fn mv(
+    client: Client,
+    src: PathBuf,
+    dst: PathBuf,
+    volume_name: &str,
+    force: bool,
+) -> Result<()> {
+    let volume_id = VolumeID::from_str(volume_name)?;
+    let dir = src.parent().unwrap_or_else(|| Path::new("/"));
+    let from = file_name(&src)?;
+    let to = file_name(&dst)?;
+    if dst.components().count() > 1 && dst.parent() != Some(dir) {
+        bail!("cannot move '{}' to another directory", src.display());
+    }
+
+    if from == to {
+        return Ok(());
+    }
+
+    let segments = client.list(dir, &volume_id).await?;
+    if !segments.iter().any(|segment| segment.name == from) {
+        return Err(Error::NotFound(src).into());
+    }
+    let existing = segments.into_iter().find(|segment| segment.name == to);
+    if let Some(segment) = existing {
+        // directories are never overwritten, which would delete everything under them
+        if segment.is_dir {
+            return Err(Error::AlreadyExists(segment.path).into());
+        }
+        if !force && !confirm(&format!("overwrite '{}'?", segment.path.display()))? {
+            return Ok(());
+        }
+        client.remove(dir, to, &volume_id).await?;
+    }
+    client.rename(dir, from, to, &volume_id).await?;
+    Ok(())
+}
+
+fn file_name(path: &Path) -> Result<&str> {
+    Ok(path
+        .file_name()
+        .ok_or_else(|| anyhow!("'{}' is not a file name", path.display()))?
+        .to_str()
+        .unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::mv;
+    use cvpn::api::mock::{listing, serve, Response, HOME_PAGE};
+    use cvpn::{api::Client, Error};
+    use std::{
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
+    #[tokio::test]
+    pub async fn mv_overwrite() {
+        let posted = Arc::new(Mutex::new(Vec::new()));
+        let posted_by_server = posted.clone();
+        let base_url = serve(move |req| {
+            if req.target == "/dana/home/index.cgi" {
+                return Response::html(HOME_PAGE);
+            }
+            if req.method == "POST" {
+                let body = req.body_text();
+                let acttype = body
+                    .split('&')
+                    .find_map(|pair| pair.strip_prefix("acttype="))
+                    .unwrap()
+                    .to_string();
+                posted_by_server.lock().unwrap().push(acttype);
+                return Response::redirect("/dana/fb/smb/wfb.cgi");
+            }
+            listing(&["dir"], &["a.txt", "b.txt"])
+        })
+        .await;
+        let client = || Client::with_base_url(base_url.clone()).unwrap();
+
+        // a directory is never removed to be overwritten
+        let err = mv(
+            client(),
+            PathBuf::from("/a.txt"),
+            PathBuf::from("dir"),
+            "fsshare",
+            true,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::AlreadyExists(p)) if p.to_str() == Some("/dir")
+        ));
+        assert!(posted.lock().unwrap().is_empty());
+
+        mv(
+            client(),
+            PathBuf::from("/a.txt"),
+            PathBuf::from("b.txt"),
+            "fsshare",
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(*posted.lock().unwrap(), ["delete", "rename"]);
+
+        // the destination is not removed if the source does not exist
+        posted.lock().unwrap().clear();
+        let err = mv(
+            client(),
+            PathBuf::from("/typo.txt"),
+            PathBuf::from("b.txt"),
+            "fsshare",
+            true,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::NotFound(p)) if p.to_str() == Some("/typo.txt")
+        ));
+        assert!(posted.lock().unwrap().is_empty());
+
+        // moving a file onto itself does nothing
+        mv(
+            client(),
+            PathBuf::from("/a.txt"),
+            PathBuf::from("a.txt"),
+            "fsshare",
+            true,
+        )
+        .await
+        .unwrap();
+        assert!(posted.lock().unwrap().is_empty());
+    }
+}
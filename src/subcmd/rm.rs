@@ -0,0 +1,64 @@
+use crate::prompt::confirm;
+use anyhow::Result;
+use cvpn::{
+    api::{model::volume_id::VolumeID, Client},
+    Error,
+};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// removes each path. errors are printed to stderr, and the last one is returned
+/// after all paths are tried.
+pub async fn rm(
+    client: Client,
+    pathes: Vec<PathBuf>,
+    volume_name: &str,
+    recursive: bool,
+    force: bool,
+) -> Result<()> {
+    let volume_id = VolumeID::from_str(volume_name)?;
+    let mut last_error = None;
+    for path in pathes {
+        if !force {
+            let prompt = if recursive {
+                format!("remove '{}' and everything under it?", path.display())
+            } else {
+                format!("remove '{}'?", path.display())
+            };
+            if !confirm(&prompt)? {
+                continue;
+            }
+        }
+        let result = if recursive {
+            client.remove_all(&path, &volume_id).await
+        } else {
+            remove(&client, &path, &volume_id).await
+        };
+        if let Err(e) = result {
+            if let Some(prev) = last_error.replace(e) {
+                eprintln!("{}", prev);
+            }
+        }
+    }
+    match last_error {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+async fn remove(client: &Client, path: &Path, volume_id: &VolumeID) -> cvpn::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::InvalidArgument(format!("cannot remove '{}'", path.display())))?
+        .to_str()
+        .unwrap();
+    client
+        .remove(
+            path.parent().unwrap_or_else(|| Path::new("/")),
+            name,
+            volume_id,
+        )
+        .await
+}
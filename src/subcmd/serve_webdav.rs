@@ -0,0 +1,412 @@
+use super::cat::ByteRange;
+use anyhow::Result;
+use chrono::{Local, NaiveDateTime, TimeZone, Utc};
+use cvpn::{
+    api::{
+        model::{segment::Segment, volume_id::VolumeID},
+        Client,
+    },
+    Error,
+};
+use futures::{stream, Future};
+use hyper::{
+    header::{
+        ACCEPT_RANGES, ALLOW, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, LAST_MODIFIED, RANGE,
+    },
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::{
+    convert::Infallible,
+    fmt::Write,
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// the characters escaped in `href`s
+const HREF_ASCIISET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+const ALLOWED_METHODS: &str = "OPTIONS, GET, HEAD, PROPFIND";
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// the state shared by all requests
+struct Gateway {
+    client: Client,
+    volume_id: VolumeID,
+}
+
+/// serves the volume as a read-only WebDAV share on `listen` until Ctrl-C
+pub async fn serve_webdav(client: Client, listen: SocketAddr, volume_name: &str) -> Result<()> {
+    let gateway = Arc::new(Gateway {
+        client,
+        volume_id: VolumeID::from_str(volume_name)?,
+    });
+    let (addr, server) = bind(gateway, &listen, async {
+        let _ = tokio::signal::ctrl_c().await;
+    })?;
+    eprintln!(
+        "serving {} on http://{}. press Ctrl-C to stop.",
+        volume_name, addr
+    );
+    server.await?;
+    Ok(())
+}
+
+/// binds the server to `listen` and returns the bound address and the server, which stops on `shutdown`
+fn bind(
+    gateway: Arc<Gateway>,
+    listen: &SocketAddr,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(SocketAddr, impl Future<Output = hyper::Result<()>>)> {
+    let make_service = make_service_fn(move |_| {
+        let gateway = gateway.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let gateway = gateway.clone();
+                async move { Ok::<_, Infallible>(gateway.handle(req).await) }
+            }))
+        }
+    });
+    let server = Server::try_bind(listen)?.serve(make_service);
+    Ok((server.local_addr(), server.with_graceful_shutdown(shutdown)))
+}
+
+impl Gateway {
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        let path = decode_path(req.uri().path());
+        let result = match req.method().as_str() {
+            "OPTIONS" => Ok(Response::builder()
+                .header("DAV", "1")
+                .header(ALLOW, ALLOWED_METHODS)
+                .body(Body::empty())
+                .unwrap()),
+            "PROPFIND" => {
+                // `infinity` is answered as 1 not to walk the whole share
+                let depth = match req.headers().get("depth").and_then(|v| v.to_str().ok()) {
+                    Some("0") => 0,
+                    _ => 1,
+                };
+                self.propfind(&path, depth).await
+            }
+            "GET" => {
+                let range = req
+                    .headers()
+                    .get(RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("bytes="))
+                    .and_then(|v| ByteRange::from_str(v).ok());
+                self.get(&path, range).await
+            }
+            "HEAD" => self.head(&path).await,
+            _ => Ok(Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .header(ALLOW, ALLOWED_METHODS)
+                .body(Body::empty())
+                .unwrap()),
+        };
+
+        result.unwrap_or_else(|e| {
+            let status = status_of(&e);
+            if status == StatusCode::BAD_GATEWAY {
+                eprintln!("{} {}: {}", req.method(), path.display(), e);
+            }
+            Response::builder()
+                .status(status)
+                .body(Body::empty())
+                .unwrap()
+        })
+    }
+
+    /// the segment of `path`. the root has no segment.
+    async fn stat(&self, path: &Path) -> cvpn::Result<Option<Segment>> {
+        if path.parent().is_none() {
+            return Ok(None);
+        }
+        let segment = self.client.stat(path, &self.volume_id).await?;
+        Ok(Some(segment))
+    }
+
+    async fn propfind(&self, path: &Path, depth: usize) -> cvpn::Result<Response<Body>> {
+        let segment = self.stat(path).await?;
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+        );
+        write_response(&mut xml, path, segment.as_ref());
+        if depth > 0 && segment.as_ref().is_none_or(|segment| segment.is_dir) {
+            let segments = self.client.list(path, &self.volume_id).await?;
+            for segment in &segments {
+                write_response(&mut xml, &segment.path, Some(segment));
+            }
+        }
+        xml.push_str("</D:multistatus>\n");
+
+        Ok(Response::builder()
+            .status(StatusCode::MULTI_STATUS)
+            .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(Body::from(xml))
+            .unwrap())
+    }
+
+    /// streams the file. the range is honored only if the server honors it.
+    async fn get(&self, path: &Path, range: Option<ByteRange>) -> cvpn::Result<Response<Body>> {
+        let (dir, name) = match (path.parent(), path.file_name()) {
+            (Some(dir), Some(name)) => (dir, name.to_str().unwrap()),
+            _ => return Ok(method_not_allowed()),
+        };
+        let start = range.map_or(0, |range| range.start);
+        let download = self
+            .client
+            .download_stream_from(dir, name, &self.volume_id, start)
+            .await?;
+
+        let mut builder = Response::builder()
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .header(ACCEPT_RANGES, "bytes");
+        let mut remaining = download.content_length();
+        match (range, download.file_size()) {
+            (Some(range), Some(size)) if download.offset() == range.start => {
+                let size = size.bytes();
+                let last = range.end.map_or(size, |end| end + 1).min(size);
+                remaining = Some(last.saturating_sub(range.start));
+                builder = builder.status(StatusCode::PARTIAL_CONTENT).header(
+                    CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", range.start, last.saturating_sub(1), size),
+                );
+            }
+            _ => (),
+        }
+        if let Some(remaining) = remaining {
+            builder = builder.header(CONTENT_LENGTH, remaining);
+        }
+
+        let chunks = stream::unfold(
+            (download, remaining),
+            |(mut download, remaining)| async move {
+                if remaining == Some(0) {
+                    return None;
+                }
+                match download.chunk().await {
+                    Ok(Some(mut chunk)) => {
+                        let remaining = remaining.map(|remaining| {
+                            chunk.truncate(remaining.min(chunk.len() as u64) as usize);
+                            remaining - chunk.len() as u64
+                        });
+                        Some((Ok(chunk), (download, remaining)))
+                    }
+                    Ok(None) => None,
+                    Err(e) => Some((Err(e), (download, Some(0)))),
+                }
+            },
+        );
+        Ok(builder.body(Body::wrap_stream(chunks)).unwrap())
+    }
+
+    async fn head(&self, path: &Path) -> cvpn::Result<Response<Body>> {
+        let mut builder = Response::builder();
+        if let Some(segment) = self.stat(path).await? {
+            if segment.is_file {
+                builder = builder
+                    .header(CONTENT_TYPE, "application/octet-stream")
+                    .header(ACCEPT_RANGES, "bytes");
+            }
+            builder = builder.header(LAST_MODIFIED, http_date(segment.uploaded_at));
+        }
+        Ok(builder.body(Body::empty()).unwrap())
+    }
+}
+
+/// decodes the request path into a remote path without `.`, `..` and trailing slashes
+fn decode_path(path: &str) -> PathBuf {
+    let decoded = percent_decode_str(path).decode_utf8_lossy();
+    let mut resolved = PathBuf::from("/");
+    for component in Path::new(decoded.as_ref()).components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => resolved.push(name),
+            _ => (),
+        }
+    }
+    resolved
+}
+
+/// writes the `response` element of `path`. `segment` is `None` for the root.
+fn write_response(xml: &mut String, path: &Path, segment: Option<&Segment>) {
+    let is_dir = segment.is_none_or(|segment| segment.is_dir);
+    let mut href = utf8_percent_encode(path.to_str().unwrap(), HREF_ASCIISET).to_string();
+    if is_dir && !href.ends_with('/') {
+        href.push('/');
+    }
+
+    xml.push_str("<D:response>");
+    write!(xml, "<D:href>{}</D:href>", href).unwrap();
+    xml.push_str("<D:propstat><D:prop>");
+    if let Some(segment) = segment {
+        write!(
+            xml,
+            "<D:displayname>{}</D:displayname><D:getlastmodified>{}</D:getlastmodified>",
+            escape(&segment.name),
+            http_date(segment.uploaded_at)
+        )
+        .unwrap();
+    }
+    if is_dir {
+        xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        xml.push_str(
+            "<D:resourcetype/><D:getcontenttype>application/octet-stream</D:getcontenttype>",
+        );
+        // sizes in listings are rounded by the server, and clients trust this length
+        if let Some(size) = segment
+            .and_then(|segment| segment.size)
+            .filter(|size| size.is_exact())
+        {
+            write!(
+                xml,
+                "<D:getcontentlength>{}</D:getcontentlength>",
+                size.bytes()
+            )
+            .unwrap();
+        }
+    }
+    xml.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// the upload date, which is in the local time, in the format of HTTP headers
+fn http_date(datetime: NaiveDateTime) -> String {
+    let datetime = Local.from_local_datetime(&datetime).earliest().map_or_else(
+        || Utc.from_utc_datetime(&datetime),
+        |datetime| datetime.with_timezone(&Utc),
+    );
+    datetime.format(HTTP_DATE_FORMAT).to_string()
+}
+
+fn method_not_allowed() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::METHOD_NOT_ALLOWED)
+        .header(ALLOW, ALLOWED_METHODS)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn status_of(e: &Error) -> StatusCode {
+    match e {
+        Error::NotFound(_) => StatusCode::NOT_FOUND,
+        Error::PermissionDenied(_) => StatusCode::FORBIDDEN,
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bind, Gateway};
+    use cvpn::api::mock::{listing, serve, Response, HOME_PAGE};
+    use cvpn::api::{model::volume_id::VolumeID, Client};
+    use reqwest::{header::RANGE, Method, StatusCode};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    const CONTENT: &[u8] = b"0123456789";
+
+    /// the session expires after the client logs in
+    #[tokio::test]
+    pub async fn serve_webdav() {
+        let logged_in = Arc::new(AtomicBool::new(false));
+        let logged_in_by_server = logged_in.clone();
+        let base_url = serve(move |req| {
+            let logged_in = &logged_in_by_server;
+            if req.target.starts_with("/dana-na/auth/url_3/login.cgi") {
+                logged_in.store(true, Ordering::Relaxed);
+                return Response::redirect("/dana/home/index.cgi");
+            }
+            if !logged_in.load(Ordering::Relaxed) {
+                return Response::redirect("/dana-na/auth/url_3/welcome.cgi");
+            }
+            if req.target.starts_with("/dana/home/index.cgi") {
+                Response::html(HOME_PAGE)
+            } else if req.target.ends_with("dir=%2F") {
+                listing(&["sub dir"], &["a.txt"])
+            } else if req.target.starts_with("/dana/fb/smb/wfb.cgi") {
+                listing(&[], &[])
+            } else if req.target.starts_with("/dana/download/a.txt") {
+                Response {
+                    status: "200 OK",
+                    headers: vec![("Content-Type", "application/octet-stream".to_string())],
+                    body: CONTENT.to_vec(),
+                }
+            } else {
+                Response::html("The file or folder does not exist on the server.")
+            }
+        })
+        .await;
+        let client = Client::with_base_url(base_url).unwrap();
+        client.login("user", "pass").await.unwrap();
+        logged_in.store(false, Ordering::Relaxed);
+        let gateway = Arc::new(Gateway {
+            client,
+            volume_id: VolumeID::FSShare,
+        });
+        let (addr, server) = bind(
+            gateway,
+            &"127.0.0.1:0".parse().unwrap(),
+            std::future::pending(),
+        )
+        .unwrap();
+        tokio::spawn(server);
+        let url = |path: &str| format!("http://{}{}", addr, path);
+        let http = reqwest::Client::new();
+        let propfind = Method::from_bytes(b"PROPFIND").unwrap();
+
+        let resp = http
+            .request(propfind.clone(), url("/"))
+            .header("Depth", "1")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+        let xml = resp.text().await.unwrap();
+        assert!(xml.contains("<D:href>/</D:href>"));
+        assert!(xml.contains("<D:href>/sub%20dir/</D:href>"));
+        assert!(xml.contains("<D:href>/a.txt</D:href>"));
+        // the listing has only the rounded size (1.0 KB) of the 10-byte file
+        assert!(!xml.contains("getcontentlength"));
+
+        let resp = http
+            .request(propfind, url("/b.txt"))
+            .header("Depth", "0")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = http.get(url("/a.txt")).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.bytes().await.unwrap(), CONTENT);
+
+        let resp = http
+            .get(url("/a.txt"))
+            .header(RANGE, "bytes=0-3")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.bytes().await.unwrap(), &CONTENT[..4]);
+    }
+}
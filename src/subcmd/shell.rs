@@ -0,0 +1,324 @@
+use super::{
+    download::{collect_targets, report, transfer, DownloadOptions, OnConflict},
+    upload::upload_pathes,
+};
+use anyhow::{anyhow, bail, Result};
+use cvpn::{
+    api::{
+        model::{segment::Segment, volume_id::VolumeID},
+        walk::DEFAULT_JOBS,
+        Client,
+    },
+    appdata::cache_dir,
+};
+use rustyline::{
+    completion::{Completer, FilenameCompleter, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::Validator,
+    Context, Editor, Helper,
+};
+use std::{
+    collections::HashMap,
+    env,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+const COMMANDS: &[&str] = &[
+    "cd", "exit", "get", "help", "lcd", "lpwd", "ls", "put", "pwd", "quit", "volume",
+];
+/// commands whose arguments are local pathes
+const LOCAL_COMMANDS: &[&str] = &["lcd", "put"];
+const HELP: &str = "\
+cd [DIR]              change the remote directory
+pwd                   print the remote directory
+ls [DIR]              list the remote directory
+get [-r] PATH...      download remote files into the local directory
+put PATH...           upload local files and directories into the remote directory
+lcd DIR               change the local directory
+lpwd                  print the local directory
+volume [NAME]         print or change the volume (e.g. fsshare)
+help                  print this help
+exit, quit            exit the shell";
+
+/// the names in the remote directories listed so far. `true` means a directory.
+type Listings = HashMap<PathBuf, Vec<(String, bool)>>;
+
+struct ShellHelper {
+    cwd: Arc<Mutex<PathBuf>>,
+    listings: Arc<Mutex<Listings>>,
+    filename_completer: FilenameCompleter,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(' ').map_or(0, |i| i + 1);
+        let word = &line[start..];
+        if start == 0 {
+            let candidates = COMMANDS
+                .iter()
+                .filter(|command| command.starts_with(word))
+                .map(|command| Pair {
+                    display: command.to_string(),
+                    replacement: format!("{} ", command),
+                })
+                .collect();
+            return Ok((0, candidates));
+        }
+
+        let command = line.split(' ').next().unwrap_or_default();
+        if LOCAL_COMMANDS.contains(&command) {
+            return self.filename_completer.complete(line, pos, ctx);
+        }
+
+        // complete the last component of the remote path from the cached listing of its parent
+        let (dir, prefix) = match word.rfind('/') {
+            Some(i) => (&word[..i + 1], &word[i + 1..]),
+            None => ("", word),
+        };
+        let listed_dir = resolve(&self.cwd.lock().unwrap(), dir);
+        let listings = self.listings.lock().unwrap();
+        let candidates = listings
+            .get(&listed_dir)
+            .into_iter()
+            .flatten()
+            .filter(|(name, _)| name.starts_with(prefix))
+            .map(|(name, is_dir)| Pair {
+                display: name.clone(),
+                replacement: format!("{}{}{}", dir, name, if *is_dir { "/" } else { "" }),
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+struct Shell {
+    client: Arc<Client>,
+    volume_name: String,
+    volume_id: Arc<VolumeID>,
+    cwd: Arc<Mutex<PathBuf>>,
+    listings: Arc<Mutex<Listings>>,
+}
+
+pub async fn shell(client: Client, volume_name: &str) -> Result<()> {
+    let cwd = Arc::new(Mutex::new(PathBuf::from("/")));
+    let listings = Arc::new(Mutex::new(HashMap::new()));
+    let mut shell = Shell {
+        client: Arc::new(client),
+        volume_name: volume_name.to_string(),
+        volume_id: Arc::new(VolumeID::from_str(volume_name)?),
+        cwd: cwd.clone(),
+        listings: listings.clone(),
+    };
+
+    let mut editor = Editor::<ShellHelper>::new()?;
+    editor.set_helper(Some(ShellHelper {
+        cwd,
+        listings,
+        filename_completer: FilenameCompleter::new(),
+    }));
+    let history_path = cache_dir().ok().map(|dir| dir.join("history.txt"));
+    if let Some(history_path) = &history_path {
+        let _ = editor.load_history(history_path);
+    }
+
+    loop {
+        let prompt = format!(
+            "cvpn:{}:{}> ",
+            shell.volume_name,
+            shell.cwd.lock().unwrap().display()
+        );
+        // reading a line blocks the thread until the user hits enter
+        let line = match tokio::task::block_in_place(|| editor.readline(&prompt)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line.as_str());
+
+        let args = match shell_words::split(&line) {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        match shell.run(&args).await {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    if let Some(history_path) = &history_path {
+        editor.save_history(history_path)?;
+    }
+    Ok(())
+}
+
+impl Shell {
+    /// runs a command and returns false if the shell should exit
+    async fn run(&mut self, args: &[String]) -> Result<bool> {
+        let (command, args) = match args.split_first() {
+            Some((command, args)) => (command.as_str(), args),
+            None => return Ok(true),
+        };
+        match command {
+            "cd" => {
+                let dir = self.resolve(args.first().map_or("/", |arg| arg.as_str()));
+                self.list(&dir).await?;
+                *self.cwd.lock().unwrap() = dir;
+            }
+            "pwd" => println!("{}", self.cwd.lock().unwrap().display()),
+            "ls" => {
+                let dir = self.resolve(args.first().map_or(".", |arg| arg.as_str()));
+                for segment in self.list(&dir).await? {
+                    println!("{}", segment);
+                }
+            }
+            "get" => {
+                let recursive = args.iter().any(|arg| arg == "-r");
+                let pathes = args
+                    .iter()
+                    .filter(|arg| *arg != "-r")
+                    .map(|arg| self.resolve(arg))
+                    .collect::<Vec<_>>();
+                if pathes.is_empty() {
+                    bail!("usage: get [-r] PATH...");
+                }
+                let options = DownloadOptions {
+                    recursive,
+                    on_conflict: OnConflict::Overwrite,
+                    resume: false,
+                    jobs: DEFAULT_JOBS,
+                    limit_rate: None,
+                };
+                let (targets, mut results) = collect_targets(
+                    &self.client,
+                    pathes,
+                    &self.volume_id,
+                    &env::current_dir()?,
+                    recursive,
+                )
+                .await?;
+                results.extend(
+                    transfer(
+                        self.client.clone(),
+                        self.volume_id.clone(),
+                        targets,
+                        options,
+                    )
+                    .await,
+                );
+                report(results)?;
+            }
+            "put" => {
+                if args.is_empty() {
+                    bail!("usage: put PATH...");
+                }
+                let cwd = self.cwd.lock().unwrap().clone();
+                upload_pathes(
+                    self.client.clone(),
+                    self.volume_id.clone(),
+                    args.iter().map(PathBuf::from).collect(),
+                    cwd.clone(),
+                    DEFAULT_JOBS,
+                )
+                .await?;
+                self.listings.lock().unwrap().remove(&cwd);
+            }
+            "lcd" => {
+                let dir = args.first().ok_or_else(|| anyhow!("usage: lcd DIR"))?;
+                env::set_current_dir(dir)?;
+            }
+            "lpwd" => println!("{}", env::current_dir()?.display()),
+            "volume" => match args.first() {
+                Some(volume_name) => {
+                    self.volume_id = Arc::new(VolumeID::from_str(volume_name)?);
+                    self.volume_name = volume_name.clone();
+                    *self.cwd.lock().unwrap() = PathBuf::from("/");
+                    self.listings.lock().unwrap().clear();
+                }
+                None => println!("{}", self.volume_name),
+            },
+            "help" => println!("{}", HELP),
+            "exit" | "quit" => return Ok(false),
+            _ => bail!("{}: command not found (try `help`)", command),
+        }
+        Ok(true)
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        resolve(&self.cwd.lock().unwrap(), path)
+    }
+
+    /// lists `dir` and remembers the names for completion
+    async fn list(&self, dir: &Path) -> Result<Vec<Segment>> {
+        let segments = self.client.list(dir, &self.volume_id).await?;
+        let names = segments
+            .iter()
+            .map(|segment| (segment.name.clone(), segment.is_dir))
+            .collect();
+        self.listings
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), names);
+        Ok(segments)
+    }
+}
+
+/// resolves `path` relative to `cwd`, removing `.` and `..`
+fn resolve(cwd: &Path, path: &str) -> PathBuf {
+    let mut resolved = PathBuf::from("/");
+    for component in cwd.join(path).components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => resolved.push(name),
+            _ => (),
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod test {
+    use super::resolve;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    pub fn resolve_path() {
+        let cwd = Path::new("/class/2022");
+        assert_eq!(resolve(cwd, "a.pdf"), PathBuf::from("/class/2022/a.pdf"));
+        assert_eq!(resolve(cwd, "../2021/./b"), PathBuf::from("/class/2021/b"));
+        assert_eq!(resolve(cwd, "/home"), PathBuf::from("/home"));
+        assert_eq!(resolve(cwd, "../../.."), PathBuf::from("/"));
+        assert_eq!(resolve(cwd, ""), PathBuf::from("/class/2022"));
+    }
+}
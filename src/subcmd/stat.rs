@@ -0,0 +1,115 @@
+use anyhow::Result;
+use cvpn::api::{
+    model::{segment::Segment, size::SizeFormat, volume_id::VolumeID},
+    Client,
+};
+use std::{
+    io::{stdout, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err("{text, json}"),
+        }
+    }
+}
+
+/// prints each path. errors are printed to stderr, and the last one is returned
+/// after all paths are tried.
+pub async fn stat(
+    client: Client,
+    pathes: Vec<PathBuf>,
+    volume_name: &str,
+    format: Format,
+) -> Result<()> {
+    let volume_id = VolumeID::from_str(volume_name)?;
+    let mut last_error = None;
+    for path in pathes {
+        let segment = match client.stat(&path, &volume_id).await {
+            Ok(segment) => segment,
+            Err(e) => {
+                if let Some(prev) = last_error.replace(e) {
+                    eprintln!("{}", prev);
+                }
+                continue;
+            }
+        };
+        let output = match format {
+            Format::Text => to_text(&segment),
+            Format::Json => to_json(&segment)?,
+        };
+        print!("{}", output);
+    }
+    stdout().flush()?;
+    match last_error {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+fn to_text(segment: &Segment) -> String {
+    let size = match &segment.size {
+        Some(size) => format!(
+            "{} ({} bytes{})",
+            size.format(SizeFormat::Unit).trim(),
+            size.bytes(),
+            if size.is_exact() { "" } else { ", approximate" }
+        ),
+        None => "-".to_string(),
+    };
+    format!(
+        "  Name: {}\n  Path: {}\n  Type: {}\n  Size: {}\n  Date: {}\nVolume: {}\n",
+        segment.name,
+        segment.path.display(),
+        if segment.is_dir { "directory" } else { "file" },
+        size,
+        segment.uploaded_at.format(DATETIME_FORMAT),
+        segment.volume_id
+    )
+}
+
+#[cfg(feature = "serde")]
+fn to_json(segment: &Segment) -> Result<String> {
+    Ok(serde_json::to_string_pretty(segment)? + "\n")
+}
+
+#[cfg(not(feature = "serde"))]
+fn to_json(_segment: &Segment) -> Result<String> {
+    anyhow::bail!("JSON output requires the `serde` feature")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{stat, Format};
+    use cvpn::api::mock::{listing, serve};
+    use cvpn::{api::Client, Error};
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    pub async fn stat_missing() {
+        let base_url = serve(|_| listing(&[], &["a.txt"])).await;
+        let client = Client::with_base_url(base_url).unwrap();
+        let pathes = ["/a.txt", "/b.txt", "/c.txt"].map(PathBuf::from).to_vec();
+        let err = stat(client, pathes, "fsshare", Format::Text)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::NotFound(p)) if p.to_str() == Some("/c.txt")
+        ));
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use cvpn::{
+    api::{config::ClientConfig, Client},
+    appdata::{cookies_saved_at, load_account_info},
+    Error,
+};
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// prints the saved account and whether the saved session is still valid
+pub async fn status(config: ClientConfig) -> Result<()> {
+    match load_account_info() {
+        Ok((username, _)) => println!("username: {}", username),
+        Err(_) => println!("username: (not saved)"),
+    }
+
+    let saved_at = match cookies_saved_at() {
+        Ok(saved_at) => DateTime::<Local>::from(saved_at),
+        Err(_) => {
+            println!("session:  none");
+            return Ok(());
+        }
+    };
+    let state = match Client::with_token(config)?.check_cookies().await {
+        Ok(()) => "valid",
+        Err(Error::SessionExpired) => "expired",
+        Err(e) => return Err(e.into()),
+    };
+    println!("session:  {}", state);
+    println!("created:  {}", saved_at.format(DATETIME_FORMAT));
+    Ok(())
+}
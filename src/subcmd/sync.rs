@@ -0,0 +1,533 @@
+use super::download::{parse_rate, report, transfer, DownloadOptions, OnConflict, Target};
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDateTime};
+use clap::Args;
+use cvpn::{
+    api::{
+        model::{segment::Segment, size::Size, volume_id::VolumeID},
+        walk::Entry,
+        Client,
+    },
+    Error,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{create_dir_all, metadata, read_dir, remove_dir, remove_file, File},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// the file in the local directory which remembers the remote files at the last sync
+const STATE_FILENAME: &str = ".cvpn-sync";
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Debug, Clone, Copy, Args)]
+pub struct SyncOptions {
+    /// delete local files which do not exist on the remote
+    #[clap(long)]
+    pub delete: bool,
+    /// print what would be done without transferring or deleting anything
+    #[clap(short = 'n', long)]
+    pub dry_run: bool,
+    /// resume partially downloaded files (*.part)
+    #[clap(long)]
+    pub resume: bool,
+    /// the maximum number of files downloaded at the same time
+    #[clap(short, long, default_value = "4")]
+    pub jobs: usize,
+    /// limit the total download speed in bytes per second (e.g. 500K, 2M)
+    #[clap(long, parse(try_from_str = parse_rate))]
+    pub limit_rate: Option<u64>,
+}
+
+/// the remote file at the last sync: (uploaded_at, size in bytes)
+type FileState = (NaiveDateTime, u64);
+
+/// what `sync` does to the local directory
+struct Plan {
+    /// the files to download, with `add` or `update` and the relative path
+    transfers: Vec<(&'static str, PathBuf, Target)>,
+    /// the state of the remote files which are not downloaded
+    unchanged: HashMap<PathBuf, FileState>,
+    /// the local directories of the remote ones
+    local_dirs: Vec<PathBuf>,
+    /// the relative pathes of the local files to delete
+    deletions: Vec<PathBuf>,
+    /// the relative pathes of the local directories to delete, sorted
+    dir_deletions: Vec<PathBuf>,
+}
+
+impl Plan {
+    /// the lines printed for the plan
+    fn lines(&self) -> Vec<String> {
+        let transfers = self
+            .transfers
+            .iter()
+            .map(|(action, rel_path, _)| format!("{:<8}{}", action, rel_path.display()));
+        let deletions = self
+            .deletions
+            .iter()
+            .map(|rel_path| format!("{:<8}{}", "delete", rel_path.display()));
+        let dir_deletions = self
+            .dir_deletions
+            .iter()
+            .map(|rel_path| format!("{:<8}{}/", "delete", rel_path.display()));
+        transfers.chain(deletions).chain(dir_deletions).collect()
+    }
+}
+
+pub async fn sync(
+    mut client: Client,
+    remote_dir: PathBuf,
+    local_dir: PathBuf,
+    volume_name: &str,
+    options: SyncOptions,
+) -> Result<()> {
+    let volume_id = VolumeID::from_str(volume_name)?;
+    if let Some(bytes_per_sec) = options.limit_rate {
+        client.limit_rate(bytes_per_sec);
+    }
+    let plan = make_plan(&client, &remote_dir, &local_dir, &volume_id, options.delete).await?;
+    for line in plan.lines() {
+        println!("{}", line);
+    }
+    if options.dry_run {
+        return Ok(());
+    }
+
+    for dir in plan.local_dirs {
+        create_dir_all(dir)?;
+    }
+    // the cause is kept for the exit status
+    let local_error = |rel_path: &Path, e: io::Error| {
+        anyhow::Error::from(Error::Io(e)).context(rel_path.display().to_string())
+    };
+    let mut last_error = None;
+    for rel_path in plan.deletions {
+        if let Err(e) = remove_file(local_dir.join(&rel_path)) {
+            let e = local_error(&rel_path, e);
+            eprintln!("{:#}", e);
+            last_error = Some(e);
+        }
+    }
+    // subdirectories are sorted after their parents, so they are removed first
+    for rel_path in plan.dir_deletions.iter().rev() {
+        match remove_dir(local_dir.join(rel_path)) {
+            // e.g. partially downloaded files are left in it
+            Err(e) if e.kind() == io::ErrorKind::DirectoryNotEmpty => (),
+            Err(e) => {
+                let e = local_error(rel_path, e);
+                eprintln!("{:#}", e);
+                last_error = Some(e);
+            }
+            Ok(()) => (),
+        }
+    }
+
+    let download_options = DownloadOptions {
+        recursive: true,
+        on_conflict: OnConflict::Overwrite,
+        resume: options.resume,
+        jobs: options.jobs,
+        limit_rate: options.limit_rate,
+    };
+    let mut new_state = plan.unchanged;
+    let targets = plan
+        .transfers
+        .into_iter()
+        .map(|(_, _, target)| target)
+        .collect();
+    let results = transfer(
+        Arc::new(client),
+        Arc::new(volume_id),
+        targets,
+        download_options,
+    )
+    .await;
+    for (target, result) in results.iter().flatten() {
+        if result.is_ok() {
+            let rel_path = target.remote_path.strip_prefix(&remote_dir)?.to_path_buf();
+            let size = target.size.map_or(0, |size| size.bytes());
+            new_state.insert(rel_path, (target.uploaded_at.unwrap(), size));
+        }
+    }
+    save_state(&local_dir, &new_state)?;
+    report(results)?;
+
+    match last_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// compares the remote directory with the local one and the state of the last sync
+async fn make_plan(
+    client: &Client,
+    remote_dir: &Path,
+    local_dir: &Path,
+    volume_id: &VolumeID,
+    delete: bool,
+) -> Result<Plan> {
+    let entries = client.walk(remote_dir, volume_id).await?;
+    let old_state = load_state(local_dir)?;
+
+    let mut plan = Plan {
+        transfers: Vec::new(),
+        unchanged: HashMap::new(),
+        local_dirs: Vec::new(),
+        deletions: Vec::new(),
+        dir_deletions: Vec::new(),
+    };
+    let mut remote_files = HashSet::new();
+    let mut remote_dirs = HashSet::new();
+    // directories which could not be listed. local files under them are never deleted.
+    let mut unlisted_dirs = Vec::new();
+    for entry in entries {
+        let segment = match entry {
+            Entry::Dir(segment) => {
+                let rel_path = segment.path.strip_prefix(remote_dir)?.to_path_buf();
+                plan.local_dirs.push(local_dir.join(&rel_path));
+                remote_dirs.insert(rel_path);
+                continue;
+            }
+            Entry::File(segment) => segment,
+            Entry::Error { path, error } => {
+                eprintln!("{}: {}", path.display(), error);
+                unlisted_dirs.push(path.strip_prefix(remote_dir)?.to_path_buf());
+                continue;
+            }
+        };
+
+        let rel_path = segment.path.strip_prefix(remote_dir)?.to_path_buf();
+        let local_path = local_dir.join(&rel_path);
+        let remote_state = (
+            segment.uploaded_at,
+            segment.size.map_or(0, |size| size.bytes()),
+        );
+        let is_unchanged = match old_state.get(&rel_path) {
+            Some(old) => *old == remote_state,
+            None => looks_unchanged(&local_path, &segment)?,
+        };
+        let action = if !local_path.exists() {
+            Some("add")
+        } else if is_unchanged {
+            None
+        } else {
+            Some("update")
+        };
+        remote_files.insert(rel_path.clone());
+        match action {
+            Some(action) => plan.transfers.push((
+                action,
+                rel_path,
+                Target {
+                    remote_path: segment.path,
+                    local_path,
+                    uploaded_at: Some(segment.uploaded_at),
+                    size: segment.size,
+                },
+            )),
+            None => {
+                plan.unchanged.insert(rel_path, remote_state);
+            }
+        }
+    }
+
+    if delete {
+        let is_unlisted =
+            |rel_path: &Path| unlisted_dirs.iter().any(|dir| rel_path.starts_with(dir));
+        for rel_path in local_files(local_dir)? {
+            if !remote_files.contains(&rel_path) && !is_unlisted(&rel_path) {
+                plan.deletions.push(rel_path);
+            }
+        }
+        for rel_path in local_subdirs(local_dir)? {
+            if !remote_dirs.contains(&rel_path) && !is_unlisted(&rel_path) {
+                plan.dir_deletions.push(rel_path);
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// without the state of the last sync, a local file is regarded as unchanged
+/// if its size matches the (rounded) remote size and it is newer than the remote one.
+fn looks_unchanged(local_path: &Path, segment: &Segment) -> Result<bool> {
+    let metadata = match metadata(local_path) {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(false),
+    };
+    let remote_size = segment.size.unwrap_or_else(Size::zero);
+    let tolerance = if remote_size.is_exact() {
+        0
+    } else {
+        remote_size.unit().bytes() / 10
+    };
+    let modified_at: DateTime<Local> = metadata.modified()?.into();
+    Ok(metadata.len().abs_diff(remote_size.bytes()) <= tolerance
+        && modified_at.naive_local() >= segment.uploaded_at)
+}
+
+/// the relative pathes of files under `dir` except the state file and *.part
+fn local_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let entries = match read_dir(&current) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                stack.push(path);
+                continue;
+            }
+            let rel_path = path.strip_prefix(dir)?.to_path_buf();
+            let is_part = path.extension().is_some_and(|ext| ext == "part");
+            if rel_path != Path::new(STATE_FILENAME) && !is_part {
+                files.push(rel_path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// the directories under `dir`, relative to `dir` and sorted
+fn local_subdirs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let entries = match read_dir(&current) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path.strip_prefix(dir)?.to_path_buf());
+                stack.push(path);
+            }
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// each line is `path<TAB>uploaded_at<TAB>size`
+fn load_state(local_dir: &Path) -> Result<HashMap<PathBuf, FileState>> {
+    let f = match File::open(local_dir.join(STATE_FILENAME)) {
+        Ok(f) => f,
+        Err(_) => return Ok(HashMap::new()),
+    };
+    let mut state = HashMap::new();
+    for line in BufReader::new(f).lines() {
+        let line = line?;
+        let tokens = line.split('\t').collect::<Vec<_>>();
+        if let [path, uploaded_at, size] = tokens[..] {
+            if let (Ok(uploaded_at), Ok(size)) = (
+                NaiveDateTime::parse_from_str(uploaded_at, DATETIME_FORMAT),
+                size.parse(),
+            ) {
+                state.insert(PathBuf::from(path), (uploaded_at, size));
+            }
+        }
+    }
+    Ok(state)
+}
+
+fn save_state(local_dir: &Path, state: &HashMap<PathBuf, FileState>) -> Result<()> {
+    create_dir_all(local_dir)?;
+    let mut entries = state.iter().collect::<Vec<_>>();
+    entries.sort();
+    let mut f = File::create(local_dir.join(STATE_FILENAME))?;
+    for (path, (uploaded_at, size)) in entries {
+        writeln!(
+            f,
+            "{}\t{}\t{}",
+            path.display(),
+            uploaded_at.format(DATETIME_FORMAT),
+            size
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        load_state, local_files, local_subdirs, make_plan, save_state, sync, SyncOptions,
+        STATE_FILENAME,
+    };
+    use chrono::NaiveDate;
+    use cvpn::api::{
+        mock::{listing, serve, Response},
+        model::volume_id::VolumeID,
+        Client,
+    };
+    use reqwest::Url;
+    use std::{
+        collections::HashMap,
+        env::temp_dir,
+        fs::{create_dir_all, read, remove_dir_all, write},
+        path::{Path, PathBuf},
+    };
+
+    /// the remote directory `/r` has `a.txt`, `b.txt`, `c.txt`, `sub/d.txt` and `locked`,
+    /// which cannot be listed. the files have the content of their names.
+    async fn serve_remote() -> Url {
+        serve(|req| {
+            if req.target.starts_with("/dana/download/") {
+                let name = req.target["/dana/download/".len()..]
+                    .split('?')
+                    .next()
+                    .unwrap();
+                return Response {
+                    status: "200 OK",
+                    headers: vec![("Content-Type", "application/octet-stream".to_string())],
+                    body: name.as_bytes().to_vec(),
+                };
+            }
+            if req.target.ends_with("dir=%2Fr") {
+                listing(&["locked", "sub"], &["a.txt", "b.txt", "c.txt"])
+            } else if req.target.ends_with("dir=%2Fr%2Fsub") {
+                listing(&[], &["d.txt"])
+            } else {
+                // the web UI is redirected to the home page if the directory cannot be read
+                Response::redirect("/dana/home/index.cgi")
+            }
+        })
+        .await
+    }
+
+    /// `b.txt` was changed on the remote since the last sync, and `c.txt` was not.
+    /// `old.txt` and `old` were removed on the remote.
+    fn make_local(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("cvpn-sync-{}-{}", name, std::process::id()));
+        create_dir_all(dir.join("old/empty")).unwrap();
+        create_dir_all(dir.join("locked")).unwrap();
+        for file in ["b.txt", "c.txt", "old.txt", "locked/keep.txt"] {
+            write(dir.join(file), b"local").unwrap();
+        }
+        let mut state = HashMap::new();
+        let at = NaiveDate::from_ymd(2022, 4, 1).and_hms(10, 0, 0);
+        state.insert(
+            PathBuf::from("b.txt"),
+            (at - chrono::Duration::days(1), 1024),
+        );
+        state.insert(PathBuf::from("c.txt"), (at, 1024));
+        save_state(&dir, &state).unwrap();
+        dir
+    }
+
+    fn options(dry_run: bool) -> SyncOptions {
+        SyncOptions {
+            delete: true,
+            dry_run,
+            resume: false,
+            jobs: 2,
+            limit_rate: None,
+        }
+    }
+
+    fn content(dir: &Path, file: &str) -> String {
+        String::from_utf8(read(dir.join(file)).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn sync_plan() {
+        let client = Client::with_base_url(serve_remote().await).unwrap();
+        let dir = make_local("plan");
+        let plan = make_plan(&client, Path::new("/r"), &dir, &VolumeID::FSShare, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            plan.lines(),
+            [
+                "add     sub/d.txt",
+                "add     a.txt",
+                "update  b.txt",
+                "delete  old.txt",
+                "delete  old/",
+                "delete  old/empty/",
+            ]
+        );
+
+        // without --delete
+        let plan = make_plan(&client, Path::new("/r"), &dir, &VolumeID::FSShare, false)
+            .await
+            .unwrap();
+        assert_eq!(plan.lines().len(), 3);
+
+        // nothing is changed with --dry-run
+        sync(
+            client,
+            PathBuf::from("/r"),
+            dir.clone(),
+            "fsshare",
+            options(true),
+        )
+        .await
+        .unwrap();
+        assert!(!dir.join("a.txt").exists());
+        assert_eq!(content(&dir, "b.txt"), "local");
+        assert!(dir.join("old.txt").exists());
+        assert!(dir.join("old/empty").exists());
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn sync_files() {
+        let client = Client::with_base_url(serve_remote().await).unwrap();
+        let dir = make_local("files");
+        sync(
+            client,
+            PathBuf::from("/r"),
+            dir.clone(),
+            "fsshare",
+            options(false),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(content(&dir, "a.txt"), "a.txt");
+        assert_eq!(content(&dir, "b.txt"), "b.txt");
+        assert_eq!(content(&dir, "c.txt"), "local");
+        assert_eq!(content(&dir, "sub/d.txt"), "d.txt");
+        assert!(!dir.join("old.txt").exists());
+        assert!(!dir.join("old").exists());
+        // the files under the directory which could not be listed are kept
+        assert_eq!(content(&dir, "locked/keep.txt"), "local");
+        assert_eq!(load_state(&dir).unwrap().len(), 4);
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    pub fn state_roundtrip() {
+        let dir = temp_dir().join(format!("cvpn-sync-test-{}", std::process::id()));
+        let at = NaiveDate::from_ymd(2022, 4, 1).and_hms(10, 0, 0);
+        let mut state = HashMap::new();
+        state.insert(PathBuf::from("a.pdf"), (at, 1024));
+        state.insert(PathBuf::from("sub/b.pptx"), (at, 12897485));
+
+        save_state(&dir, &state).unwrap();
+        assert_eq!(load_state(&dir).unwrap(), state);
+
+        create_dir_all(dir.join("sub")).unwrap();
+        write(dir.join("sub/b.pptx"), b"").unwrap();
+        write(dir.join("c.txt.part"), b"").unwrap();
+        let files = local_files(&dir).unwrap();
+        assert_eq!(files, [PathBuf::from("sub/b.pptx")]);
+        create_dir_all(dir.join("sub/empty")).unwrap();
+        let dirs = local_subdirs(&dir).unwrap();
+        assert_eq!(dirs, [PathBuf::from("sub"), PathBuf::from("sub/empty")]);
+        assert!(dir.join(STATE_FILENAME).exists());
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
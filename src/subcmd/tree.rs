@@ -0,0 +1,187 @@
+use anyhow::Result;
+use cvpn::api::{
+    model::{
+        size::{Size, SizeFormat},
+        volume_id::VolumeID,
+    },
+    walk::{Children, Node},
+    Client,
+};
+use std::{
+    io::{stdout, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+#[derive(Default)]
+struct Summary {
+    n_dirs: usize,
+    n_files: usize,
+    bytes: u64,
+    exact: bool,
+}
+
+impl Summary {
+    fn new() -> Self {
+        Self {
+            exact: true,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, other: &Summary) {
+        self.n_dirs += other.n_dirs;
+        self.n_files += other.n_files;
+        self.bytes += other.bytes;
+        self.exact &= other.exact;
+    }
+
+    fn size(&self) -> String {
+        format!(
+            "{}{}",
+            if self.exact { "" } else { "~" },
+            Size::from_bytes(self.bytes)
+                .format(SizeFormat::Binary)
+                .trim()
+        )
+    }
+}
+
+pub async fn tree(
+    client: Client,
+    path: PathBuf,
+    volume_name: &str,
+    depth: Option<usize>,
+    jobs: usize,
+) -> Result<()> {
+    let volume_id = VolumeID::from_str(volume_name)?;
+    let nodes = client.walk_tree(&path, &volume_id, depth, jobs).await?;
+
+    let mut output = format!("{}\n", path.display());
+    let summary = write_nodes(&mut output, &nodes, "");
+    output.push_str(&format!(
+        "\n{} directories, {} files, {}\n",
+        summary.n_dirs,
+        summary.n_files,
+        summary.size()
+    ));
+    print!("{}", output);
+    stdout().flush()?;
+    Ok(())
+}
+
+/// writes `nodes` with `prefix` and returns the summary of them
+fn write_nodes(output: &mut String, nodes: &[Node], prefix: &str) -> Summary {
+    let mut summary = Summary::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let is_last = i + 1 == nodes.len();
+        let (branch, indent) = if is_last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        match node {
+            Node::File(segment) => {
+                summary.n_files += 1;
+                let size = segment.size.unwrap_or_else(Size::zero);
+                summary.bytes += size.bytes();
+                summary.exact &= size.is_exact();
+                output.push_str(&format!(
+                    "{}{}{}  ({}, {})\n",
+                    prefix,
+                    branch,
+                    segment.name,
+                    size.format(SizeFormat::Binary).trim(),
+                    segment.uploaded_at.format(DATETIME_FORMAT)
+                ));
+            }
+            Node::Dir { segment, children } => {
+                summary.n_dirs += 1;
+                let line = format!("{}{}{}/", prefix, branch, segment.name);
+                match children {
+                    Children::Listed(children) => {
+                        let mut buf = String::new();
+                        let child_summary =
+                            write_nodes(&mut buf, children, &format!("{}{}", prefix, indent));
+                        output.push_str(&format!(
+                            "{}  ({} files, {})\n",
+                            line,
+                            child_summary.n_files,
+                            child_summary.size()
+                        ));
+                        output.push_str(&buf);
+                        summary.add(&child_summary);
+                    }
+                    Children::Unlisted => output.push_str(&format!("{}\n", line)),
+                    Children::Error(e) => output.push_str(&format!("{}  [{}]\n", line, e)),
+                }
+            }
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod test {
+    use super::write_nodes;
+    use chrono::NaiveDate;
+    use cvpn::{
+        api::{
+            model::{
+                segment::Segment,
+                size::{Size, Unit},
+            },
+            walk::{Children, Node},
+        },
+        Error,
+    };
+    use std::path::PathBuf;
+
+    #[test]
+    pub fn write_tree() {
+        let at = NaiveDate::from_ymd(2022, 4, 1).and_hms(10, 0, 0);
+        let file = |name: &str, size: Size| {
+            Node::File(Segment::from_file(
+                name.to_string(),
+                PathBuf::from("/class").join(name),
+                size,
+                String::new(),
+                at,
+            ))
+        };
+        let dir = |name: &str, children: Children| Node::Dir {
+            segment: Segment::from_dir(
+                name.to_string(),
+                PathBuf::from("/class").join(name),
+                String::new(),
+                at,
+            ),
+            children,
+        };
+        let nodes = vec![
+            dir(
+                "a",
+                Children::Listed(vec![file("x.pdf", Size::new(1.5, Unit::KB))]),
+            ),
+            dir(
+                "b",
+                Children::Error(Error::PermissionDenied(PathBuf::from("/class/b"))),
+            ),
+            file("y.txt", Size::new(512.0, Unit::B)),
+        ];
+
+        let mut output = String::new();
+        let summary = write_nodes(&mut output, &nodes, "");
+        assert_eq!(
+            output,
+            "├── a/  (1 files, ~1.50[KiB])\n\
+             │   └── x.pdf  (1.50[KiB], 2022-04-01 10:00)\n\
+             ├── b/  [/class/b: Permission denied]\n\
+             └── y.txt  (512[B], 2022-04-01 10:00)\n"
+        );
+        assert_eq!((summary.n_dirs, summary.n_files), (2, 2));
+        assert_eq!(summary.bytes, 1536 + 512);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::progress::Progress;
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use cvpn::{
+    api::{model::volume_id::VolumeID, Client},
+    Error,
+};
+use futures::{future::join_all, stream};
+use indicatif::HumanBytes;
+use std::{
+    fs::{metadata, read_dir},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use tokio::{fs::File, io::AsyncReadExt, sync::Semaphore};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+struct Target {
+    local_path: PathBuf,
+    remote_dir: PathBuf,
+    filename: String,
+}
+
+pub async fn upload(
+    mut client: Client,
+    pathes: Vec<PathBuf>,
+    remote_dir: PathBuf,
+    volume_name: &str,
+    jobs: usize,
+    limit_rate: Option<u64>,
+) -> Result<()> {
+    if let Some(bytes_per_sec) = limit_rate {
+        client.limit_rate(bytes_per_sec);
+    }
+    let volume_id_arc = Arc::new(VolumeID::from_str(volume_name)?);
+    upload_pathes(Arc::new(client), volume_id_arc, pathes, remote_dir, jobs).await
+}
+
+/// uploads local files and directories into `remote_dir` and prints the result of each file.
+/// the error of the last failed file or directory is returned.
+pub async fn upload_pathes(
+    client_arc: Arc<Client>,
+    volume_id_arc: Arc<VolumeID>,
+    pathes: Vec<PathBuf>,
+    remote_dir: PathBuf,
+    jobs: usize,
+) -> Result<()> {
+    // directories are made in pre-order so that parents exist before their children
+    let mut dirs = Vec::new();
+    let mut targets = Vec::new();
+    for path in pathes {
+        let filename = match path.file_name() {
+            Some(filename) => filename.to_string_lossy().to_string(),
+            None => {
+                eprintln!("{}: path must be filepath", path.display());
+                continue;
+            }
+        };
+        if path.is_dir() {
+            collect_dir(&path, &remote_dir, &filename, &mut dirs, &mut targets)?;
+        } else {
+            targets.push(Target {
+                local_path: path,
+                remote_dir: remote_dir.clone(),
+                filename,
+            });
+        }
+    }
+
+    let mut last_error = None;
+    for (parent, name) in dirs {
+        match client_arc.mkdir(&parent, &name, &volume_id_arc).await {
+            Ok(()) | Err(Error::AlreadyExists(_)) => (),
+            Err(e) => {
+                eprintln!("{}: {}", parent.join(&name).display(), e);
+                last_error = Some(e.into());
+            }
+        }
+    }
+
+    let progress_arc = Arc::new(Progress::new(targets.len()));
+    let semaphore_arc = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut handles = vec![];
+    for target in targets {
+        let client = client_arc.clone();
+        let volume_id = volume_id_arc.clone();
+        let progress = progress_arc.clone();
+        let semaphore = semaphore_arc.clone();
+        let handle = tokio::spawn(async move {
+            let _permit = semaphore.acquire().await.unwrap();
+            let result = upload_file(&client, &target, &volume_id, &progress).await;
+            progress.finish_file();
+            (target, result)
+        });
+        handles.push(handle);
+    }
+    let results = join_all(handles).await;
+    progress_arc.finish();
+
+    let (mut n_uploaded, mut n_failed, mut n_bytes) = (0, 0, 0);
+    for join_res in results {
+        match join_res {
+            Err(e) => {
+                n_failed += 1;
+                eprintln!("failed to join an async handle{}", e);
+                last_error = Some(e.into());
+            }
+            Ok((target, Err(e))) => {
+                n_failed += 1;
+                eprintln!("{:<12}{}: {}", "failed", target.local_path.display(), e);
+                last_error = Some(e);
+            }
+            Ok((target, Ok(size))) => {
+                n_uploaded += 1;
+                n_bytes += size;
+                eprintln!(
+                    "{:<12}{} ({})",
+                    "uploaded",
+                    target.remote_dir.join(&target.filename).display(),
+                    HumanBytes(size)
+                );
+            }
+        }
+    }
+    eprintln!(
+        "{} uploaded, {} failed ({} transferred)",
+        n_uploaded,
+        n_failed,
+        HumanBytes(n_bytes)
+    );
+    match last_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// collects the subdirectories and files under `local_dir`, which is uploaded as `remote_parent/name`
+fn collect_dir(
+    local_dir: &Path,
+    remote_parent: &Path,
+    name: &str,
+    dirs: &mut Vec<(PathBuf, String)>,
+    targets: &mut Vec<Target>,
+) -> Result<()> {
+    dirs.push((remote_parent.to_path_buf(), name.to_string()));
+    let remote_dir = remote_parent.join(name);
+
+    let mut entries = read_dir(local_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for path in entries {
+        let filename = path
+            .file_name()
+            .ok_or_else(|| anyhow!("path must be filepath"))?
+            .to_string_lossy()
+            .to_string();
+        if path.is_dir() {
+            collect_dir(&path, &remote_dir, &filename, dirs, targets)?;
+        } else {
+            targets.push(Target {
+                local_path: path,
+                remote_dir: remote_dir.clone(),
+                filename,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// uploads the file with a progress bar and returns the number of bytes uploaded
+async fn upload_file(
+    client: &Client,
+    target: &Target,
+    volume_id: &VolumeID,
+    progress: &Progress,
+) -> Result<u64> {
+    let size = metadata(&target.local_path)?.len();
+    let f = File::open(&target.local_path).await?;
+    let file_progress = progress.start(&target.local_path.to_string_lossy(), Some(size), 0);
+
+    // the file is read chunk by chunk while it is sent
+    let chunks = stream::unfold(
+        (f, file_progress),
+        |(mut f, mut file_progress)| async move {
+            let mut buf = vec![0; CHUNK_SIZE];
+            match f.read(&mut buf).await {
+                Ok(0) => {
+                    file_progress.finish();
+                    None
+                }
+                Ok(n) => {
+                    buf.truncate(n);
+                    file_progress.inc(n as u64);
+                    Some((Ok(Bytes::from(buf)), (f, file_progress)))
+                }
+                Err(e) => Some((Err(e), (f, file_progress))),
+            }
+        },
+    );
+    client
+        .upload_stream(&target.remote_dir, &target.filename, volume_id, chunks)
+        .await?;
+    Ok(size)
+}